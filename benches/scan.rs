@@ -0,0 +1,115 @@
+use std::fs;
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use tempfile::TempDir;
+use v8find4rs::v8_env::MemoryEnvironment;
+use v8find4rs::v8_finder::{SearchPriority, V8Finder};
+
+const ROOTS: usize = 3;
+const PLATFORMS_PER_ROOT: usize = 100;
+// Размер исполняемого файла на диске, близкий к реальному 1cv8s.exe. Файлы создаются разреженными
+const EXE_LEN: u64 = 8 * 1024 * 1024;
+
+// Минимальный PE заголовок 64-х разрядного исполняемого файла
+fn pe_header() -> Vec<u8> {
+    let mut header = vec![0u8; 0x200];
+    header[..2].copy_from_slice(b"MZ");
+    header[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+    header[0x80..0x84].copy_from_slice(b"PE\0\0");
+    header[0x84..0x86].copy_from_slice(&0x8664u16.to_le_bytes());
+    header
+}
+
+// Исполняемый файл на диске в том виде, в каком его ищет текущая ОС
+#[cfg(windows)]
+const DISK_EXE: &str = "bin/1cv8s.exe";
+#[cfg(not(windows))]
+const DISK_EXE: &str = "1cv8s";
+
+fn disk_header() -> Vec<u8> {
+    if cfg!(windows) {
+        return pe_header();
+    }
+
+    let mut header = vec![0u8; 0x200];
+    if cfg!(target_os = "macos") {
+        // Mach-O 64, cputype x86_64
+        header[..4].copy_from_slice(&0xfeedfacfu32.to_le_bytes());
+        header[4..8].copy_from_slice(&0x0100_0007u32.to_le_bytes());
+    } else {
+        // ELF64, little-endian, e_machine x86_64
+        header[..4].copy_from_slice(b"\x7fELF");
+        header[4] = 2;
+        header[5] = 1;
+        header[18..20].copy_from_slice(&62u16.to_le_bytes());
+    }
+    header
+}
+
+fn platform_dirs() -> impl Iterator<Item=(usize, String)> {
+    (0..ROOTS).flat_map(|root| (0..PLATFORMS_PER_ROOT)
+        .map(move |build| (root, format!("8.3.{}.{}", 10 + root, 1000 + build))))
+}
+
+fn memory_tree() -> MemoryEnvironment {
+    let exe = pe_header();
+    let mut env = MemoryEnvironment::new("windows");
+    for root in 0..ROOTS {
+        env = env.with_dir(format!(r"C:\1c\root{}", root));
+    }
+    for (root, version) in platform_dirs() {
+        env = env.with_file(format!(r"C:\1c\root{}\{}\bin\1cv8s.exe", root, version), exe.clone());
+    }
+    env
+}
+
+fn disk_tree() -> TempDir {
+    let tree = tempfile::tempdir().unwrap();
+    for (root, version) in platform_dirs() {
+        let exe_path = tree.path().join(format!("root{}", root)).join(version).join(DISK_EXE);
+        fs::create_dir_all(exe_path.parent().unwrap()).unwrap();
+        fs::write(&exe_path, disk_header()).unwrap();
+        fs::File::options().write(true).open(&exe_path).unwrap().set_len(EXE_LEN).unwrap();
+    }
+    tree
+}
+
+fn disk_roots(tree: &Path) -> Vec<std::path::PathBuf> {
+    (0..ROOTS).map(|root| tree.join(format!("root{}", root))).collect()
+}
+
+fn scan(c: &mut Criterion) {
+    let env = memory_tree();
+    c.bench_function("memory: scan 300 platforms", |b| b.iter_batched(
+        || env.clone(),
+        |env| V8Finder::builder().no_defaults().roots(["C:/1c/root0", "C:/1c/root1", "C:/1c/root2"])
+            .environment(env).build(),
+        BatchSize::LargeInput,
+    ));
+    c.bench_function("memory: scan 300 platforms and detect arch", |b| b.iter_batched(
+        || env.clone(),
+        |env| {
+            let finder = V8Finder::builder().no_defaults().roots(["C:/1c/root0", "C:/1c/root1", "C:/1c/root2"])
+                .environment(env).build();
+            assert!(finder.get_platform("8.3", SearchPriority::X64).is_some());
+        },
+        BatchSize::LargeInput,
+    ));
+
+    let tree = disk_tree();
+    let roots = disk_roots(tree.path());
+    c.bench_function("disk: scan 300 platforms", |b| b.iter(
+        || V8Finder::builder().no_defaults().roots(roots.clone()).build()));
+    c.bench_function("disk: scan 300 platforms and query one version", |b| b.iter(|| {
+        let finder = V8Finder::builder().no_defaults().roots(roots.clone()).build();
+        assert!(finder.get_platform("8.3.12.1050", SearchPriority::X64).is_some());
+    }));
+    c.bench_function("disk: scan 300 platforms and detect arch", |b| b.iter(|| {
+        let finder = V8Finder::builder().no_defaults().roots(roots.clone()).build();
+        assert!(finder.get_platform("8.3", SearchPriority::X64).is_some());
+    }));
+}
+
+criterion_group!(benches, scan);
+criterion_main!(benches);
@@ -9,3 +9,15 @@ pub mod v8_finder;
 /// Модуль предоставляющий абстракции как для имен приложений платформы 1С (rac, ras, толстый клиент),
 /// так и для расчета разрядности платформы 1С
 pub mod v8_app;
+
+/// Модуль описывающий версию платформы 1С
+pub mod v8_version;
+
+/// Модуль описывающий ошибки поиска платформ 1С
+pub mod v8_error;
+
+/// Модуль описывающий окружение поиска: ОС, переменные окружения и файловую систему
+pub mod v8_env;
+
+/// Модуль для чтения и записи файла настроек стартера 1CEStart.cfg
+pub mod v8_starter;
@@ -1,16 +1,32 @@
-use std::path::PathBuf;
-use std::fs::File;
-use std::io::Read;
-use exe::{PE, Arch};
-use std::env;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::ErrorKind;
+use std::iter::FromIterator;
+use std::path::{Path, PathBuf};
+use log::warn;
+use crate::v8_env::{Environment, SystemEnvironment};
+use crate::v8_error::V8FindError;
+
+pub(crate) mod v8_binary;
+mod v8_app_layout;
+
+pub use v8_app_layout::V8AppLayout;
 
 /// Перечень возможных приложений 1С. Необходим для поиска пути к данным приложениям при использовании
-/// V8Finder
+/// V8Finder. Расположение файла приложения в каталоге платформы описывается [V8AppLayout]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum V8AppType {
     /// Толстый клиент
     ThickClient,
     /// Тонкий клиент
     ThinClient,
+    /// Толстый клиент с выбором информационной базы при запуске (1cv8s)
+    ThickClientSelector,
+    /// Стартер 1С: Предприятия (1cestart). В Windows находится в каталоге common рядом с каталогами версий
+    Starter,
+    /// Толстый клиент учебной версии (1cv8t)
+    TrainingClient,
+    /// Тонкий клиент учебной версии (1cv8ct)
+    TrainingThinClient,
     /// Клиент удаленного администрирования
     RAC,
     /// Сервер удаленного администрирования
@@ -19,94 +35,614 @@ pub enum V8AppType {
     DBGS,
     /// Сервер приложений
     AppServer,
+    /// Менеджер кластера (rmngr)
+    ClusterManager,
+    /// Рабочий процесс (rphost)
+    WorkingProcess,
     /// Сервер хранилища
     RepositoryServer,
+    /// Клиент хранилища конфигураций командной строки (crcmd)
+    RepositoryClient,
     /// Автономный сервер
     IBSRV,
     /// Утилита администрирования
     IBCMD,
+    /// Утилита публикации на веб-сервере (webinst)
+    WebInst,
+    /// Тестирование и исправление файловой информационной базы (chdbfl)
+    CHDBFL,
+    /// Конвертация файловой информационной базы (cnvdbfl)
+    CNVDBFL,
+    /// Приложение с произвольным именем файла без расширения, например `"1cv8ct"` или `"ibcmd"`
+    Custom(String),
 }
 
 impl V8AppType {
     /// Возвращает текстовое имя соответствующего вида приложения 1С
     pub fn value(&self) -> &str {
-        match *self {
+        match self {
             V8AppType::ThickClient => "1cv8",
             V8AppType::ThinClient => "1cv8c",
+            V8AppType::ThickClientSelector => "1cv8s",
+            V8AppType::Starter => "1cestart",
+            V8AppType::TrainingClient => "1cv8t",
+            V8AppType::TrainingThinClient => "1cv8ct",
             V8AppType::RAC => "rac",
             V8AppType::RAS => "ras",
             V8AppType::DBGS => "dbgs",
             V8AppType::AppServer => "ragent",
+            V8AppType::ClusterManager => "rmngr",
+            V8AppType::WorkingProcess => "rphost",
             V8AppType::RepositoryServer => "crserver",
+            V8AppType::RepositoryClient => "crcmd",
             V8AppType::IBSRV => "ibsrv",
             V8AppType::IBCMD => "ibcmd",
+            V8AppType::WebInst => "webinst",
+            V8AppType::CHDBFL => "chdbfl",
+            V8AppType::CNVDBFL => "cnvdbfl",
+            V8AppType::Custom(name) => name,
+        }
+    }
+}
+
+/// Компонент платформы 1С: приложение, набор связанных приложений или модуль веб-сервера.
+/// Состав установленных компонентов возвращает [V8Platform::components](crate::v8_finder::V8Platform::components)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum V8Component {
+    /// Толстый клиент (1cv8)
+    ThickClient,
+    /// Тонкий клиент (1cv8c)
+    ThinClient,
+    /// Сервер 1С: Предприятия - агент, менеджер и рабочий процесс (ragent, rmngr, rphost)
+    Server,
+    /// Сервер удаленного администрирования (ras)
+    RAS,
+    /// Клиент удаленного администрирования (rac)
+    RAC,
+    /// Сервер хранилища конфигураций (crserver)
+    RepositoryServer,
+    /// Автономный сервер (ibsrv)
+    IBSRV,
+    /// Утилита администрирования, в том числе выгрузки и загрузки информационных баз (ibcmd)
+    IBCMD,
+    /// Сервер отладки (dbgs)
+    DBGS,
+    /// Модуль расширения веб-сервера Apache (wsap24, wsap22 или wsap20)
+    ApacheModule,
+    /// Модуль расширения веб-сервера IIS (wsisapi)
+    IISModule,
+    /// Утилита публикации на веб-сервере (webinst)
+    WebInst,
+    /// Утилиты проверки и конвертации файловых баз (chdbfl, cnvdbfl)
+    DbFileTools,
+    /// Толстый или тонкий клиент учебной версии (1cv8t, 1cv8ct)
+    TrainingClient,
+}
+
+impl V8Component {
+    /// Все компоненты в порядке объявления
+    pub const ALL: [V8Component; 14] = [
+        V8Component::ThickClient, V8Component::ThinClient, V8Component::Server, V8Component::RAS,
+        V8Component::RAC, V8Component::RepositoryServer, V8Component::IBSRV, V8Component::IBCMD,
+        V8Component::DBGS, V8Component::ApacheModule, V8Component::IISModule, V8Component::WebInst,
+        V8Component::DbFileTools, V8Component::TrainingClient,
+    ];
+
+    // Имена приложений и модулей (см. V8AppType::value) в V8AppLayout. Компонент установлен,
+    // если найдены все файлы из `all` и хотя бы один из `any`
+    fn files(&self) -> (&'static [&'static str], &'static [&'static str]) {
+        match self {
+            V8Component::ThickClient => (&["1cv8"], &[]),
+            V8Component::ThinClient => (&["1cv8c"], &[]),
+            V8Component::Server => (&["ragent", "rmngr", "rphost"], &[]),
+            V8Component::RAS => (&["ras"], &[]),
+            V8Component::RAC => (&["rac"], &[]),
+            V8Component::RepositoryServer => (&["crserver"], &[]),
+            V8Component::IBSRV => (&["ibsrv"], &[]),
+            V8Component::IBCMD => (&["ibcmd"], &[]),
+            V8Component::DBGS => (&["dbgs"], &[]),
+            V8Component::ApacheModule => (&[], &["wsap24", "wsap22", "wsap20"]),
+            V8Component::IISModule => (&["wsisapi"], &[]),
+            V8Component::WebInst => (&["webinst"], &[]),
+            V8Component::DbFileTools => (&[], &["chdbfl", "cnvdbfl"]),
+            V8Component::TrainingClient => (&[], &["1cv8t", "1cv8ct"]),
+        }
+    }
+
+    /// Является ли компонент клиентским приложением
+    pub fn is_client(&self) -> bool {
+        matches!(self, V8Component::ThickClient | V8Component::ThinClient | V8Component::TrainingClient)
+    }
+
+    /// Определяет состав установленных компонентов платформы. Файлы ищутся там же, где их находит
+    /// [V8Platform::get_app_by_type](crate::v8_finder::V8Platform::get_app_by_type), то есть по стандартному
+    /// [V8AppLayout]. Каждый каталог при этом читается не более одного раза
+    pub(crate) fn detect(env: &dyn Environment, v8_path: &Path) -> V8Components {
+        let os = env.os();
+        let layout = V8AppLayout::new();
+        let mut listing = DirListing::new(env, v8_path);
+        let mut installed = |name: &&str| layout.candidates_by_name(name, os, v8_path).iter()
+            .any(|path| listing.contains(path));
+
+        let components = V8Component::ALL.iter()
+            .copied()
+            .filter(|component| {
+                let (all, any) = component.files();
+                all.iter().all(&mut installed) && (any.is_empty() || any.iter().any(&mut installed))
+            })
+            .collect();
+
+        V8Components { components }
+    }
+}
+
+// Содержимое прочитанных каталогов. Каталог внутри каталога версии читается, только если он есть
+// в содержимом родительского, так что отсутствующие 1cv8.app и т.п. не читаются вовсе. Имена сравниваются
+// без учета регистра только в Windows, как и при проверке существования файла в V8Platform::get_app
+struct DirListing<'a> {
+    env: &'a dyn Environment,
+    v8_path: &'a Path,
+    ignore_case: bool,
+    dirs: HashMap<PathBuf, HashSet<String>>,
+}
+
+impl<'a> DirListing<'a> {
+    fn new(env: &'a dyn Environment, v8_path: &'a Path) -> Self {
+        DirListing { env, v8_path, ignore_case: env.os() == "windows", dirs: HashMap::new() }
+    }
+
+    fn contains(&mut self, path: &Path) -> bool {
+        match (path.parent(), path.file_name().and_then(|name| name.to_str())) {
+            (Some(parent), Some(name)) => {
+                let name = self.normalize(name);
+                self.names(parent).contains(&name)
+            }
+            _ => false,
+        }
+    }
+
+    fn names(&mut self, dir: &Path) -> &HashSet<String> {
+        if !self.dirs.contains_key(dir) {
+            let is_nested = dir.starts_with(self.v8_path) && dir != self.v8_path;
+            let names = if is_nested && !self.contains(dir) {
+                HashSet::new()
+            } else {
+                self.read(dir)
+            };
+            self.dirs.insert(dir.to_path_buf(), names);
+        }
+        &self.dirs[dir]
+    }
+
+    fn read(&self, dir: &Path) -> HashSet<String> {
+        match self.env.read_dir(dir) {
+            Ok(paths) => paths.iter()
+                .filter_map(|path| path.file_name().and_then(|name| name.to_str()))
+                .map(|name| self.normalize(name))
+                .collect(),
+            Err(err) => {
+                if err.kind() != ErrorKind::NotFound {
+                    warn!("Can't read {}: {}", dir.display(), err);
+                }
+                HashSet::new()
+            }
+        }
+    }
+
+    fn normalize(&self, name: &str) -> String {
+        if self.ignore_case { name.to_lowercase() } else { name.to_owned() }
+    }
+}
+
+/// Набор установленных компонентов платформы 1С
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+pub struct V8Components {
+    components: BTreeSet<V8Component>,
+}
+
+impl V8Components {
+    /// Установлен ли компонент
+    pub fn contains(&self, component: V8Component) -> bool {
+        self.components.contains(&component)
+    }
+
+    /// Установленные компоненты в порядке объявления [V8Component]
+    pub fn iter(&self) -> impl Iterator<Item=V8Component> + '_ {
+        self.components.iter().copied()
+    }
+
+    /// Не найдено ни одного компонента
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    /// Установлен ли толстый или тонкий клиент
+    pub fn has_client(&self) -> bool {
+        self.iter().any(|component| component.is_client())
+    }
+
+    /// Установлен сервер 1С: Предприятия, но не установлен ни один клиент
+    pub fn is_server_only(&self) -> bool {
+        self.contains(V8Component::Server) && !self.has_client()
+    }
+}
+
+impl FromIterator<V8Component> for V8Components {
+    fn from_iter<I: IntoIterator<Item=V8Component>>(iter: I) -> Self {
+        V8Components { components: iter.into_iter().collect() }
+    }
+}
+
+/// Вариант поставки платформы 1С
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum V8Edition {
+    /// Полная установка: толстый клиент и, возможно, сервер
+    Full,
+    /// Только тонкий клиент
+    ThinClientOnly,
+    /// Учебная версия. Не выбирается [V8Finder::get_platform](crate::v8_finder::V8Finder::get_platform)
+    /// и [V8Finder::find_platform](crate::v8_finder::V8Finder::find_platform)
+    Training,
+    /// Только сервер, без клиентов
+    ServerOnly,
+    /// Не найдено ни одного компонента: каталог версии пуст, установка не завершена или файлы удалены.
+    /// Не выбирается [V8Finder::get_platform](crate::v8_finder::V8Finder::get_platform)
+    /// и [V8Finder::find_platform](crate::v8_finder::V8Finder::find_platform)
+    Unknown,
+}
+
+impl V8Edition {
+    /// Определяет вариант поставки по составу компонентов и каталогу платформы. Учебной считается
+    /// платформа с клиентами 1cv8t/1cv8ct или расположенная в каталоге 1cv8t. Если не найдено ни одного
+    /// компонента, возвращается [V8Edition::Unknown]
+    pub fn detect(components: &V8Components, v8_path: &Path) -> V8Edition {
+        if components.is_empty() {
+            return V8Edition::Unknown;
+        }
+
+        let in_training_root = v8_path.to_str().unwrap_or("")
+            .split(['\\', '/'])
+            .any(|name| name.eq_ignore_ascii_case("1cv8t"));
+
+        if in_training_root || components.contains(V8Component::TrainingClient) {
+            V8Edition::Training
+        } else if components.is_server_only() {
+            V8Edition::ServerOnly
+        } else if components.contains(V8Component::ThinClient) && !components.contains(V8Component::ThickClient)
+            && !components.contains(V8Component::Server) {
+            V8Edition::ThinClientOnly
+        } else {
+            V8Edition::Full
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-/// Перечень разрядностей платформы 1С
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Перечень архитектур платформы 1С
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum V8Arch {
-    /// 32-х разрядная
+    /// 32-х разрядная x86
     X86,
-    /// 64-х разрядная
+    /// 64-х разрядная x86_64
     X64,
+    /// 64-х разрядная ARM (aarch64 в Linux, Apple Silicon в macOS)
+    Arm64,
+    /// Эльбрус (e2k)
+    E2k,
+    /// Архитектуру определить не удалось. Такие платформы не выбираются ни одним из
+    /// [SearchPriority](crate::v8_finder::SearchPriority)
+    Unknown,
 }
 
+/// Способ, которым была определена архитектура платформы
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum V8ArchSource {
+    /// По PE заголовку исполняемого файла (Windows)
+    PeHeader,
+    /// По ELF заголовку исполняемого файла (Linux)
+    ElfHeader,
+    /// По Mach-O заголовку исполняемого файла (macOS)
+    MachOHeader,
+    /// По имени каталога установки: i386, x86_64, aarch64, e2k
+    PathHeuristic,
+    /// Архитектуру определить не удалось, она считается [V8Arch::Unknown]
+    Default,
+    /// Архитектура задана явно при создании [V8Platform](crate::v8_finder::V8Platform)
+    Explicit,
+}
+
+const WINDOWS_BINARIES: &[&str] = &[
+    "bin/1cv8s.exe", "bin/1cv8.exe", "bin/1cv8c.exe", "bin/ragent.exe", "bin/1cv8t.exe", "bin/1cv8ct.exe",
+];
+const LINUX_BINARIES: &[&str] = &["1cv8s", "1cv8", "1cv8c", "ragent", "1cv8t", "1cv8ct"];
+const MACOS_BINARIES: &[&str] = &[
+    "1cv8s", "1cv8", "1cv8c", "ragent", "1cv8.app/Contents/MacOS/1cv8", "1cv8c.app/Contents/MacOS/1cv8c",
+    "1cv8t", "1cv8ct",
+];
+
 impl V8Arch {
-    /// Осуществляет попытку определения разрядности платформы 1С. Логика определения различается в
+    /// Осуществляет попытку определения архитектуры платформы 1С. Логика определения различается в
     /// зависимости от текущей ОС:
-    /// * Windows - по файлу 1cv8s.exe находящегося в папке bin. Читается его PE
-    /// [сигнатура](https://docs.microsoft.com/en-us/windows/win32/debug/pe-format#machine-types)
-    /// * Linux - по пути платформы. 1С автоматически устанавливает платформу в папки i386 или x86_64, соответственно
-    /// разрядность определяется по наличию одной из подстрок в пути;
-    /// * macOS - всегда 64-х разрядная.
+    /// * Windows - по PE [заголовку](https://docs.microsoft.com/en-us/windows/win32/debug/pe-format#machine-types)
+    ///   первого найденного из файлов 1cv8s.exe, 1cv8.exe, 1cv8c.exe, ragent.exe, 1cv8t.exe, 1cv8ct.exe в папке bin.
+    ///   Если ни один из них прочитать не удалось - [V8Arch::Unknown];
+    /// * Linux - по ELF заголовку (класс и `e_machine`) первого найденного из файлов 1cv8s, 1cv8, 1cv8c, ragent,
+    ///   1cv8t, 1cv8ct в каталоге платформы. Если ни один из них прочитать не удалось - по пути платформы: 1С
+    ///   устанавливает платформу в папки i386, x86_64, aarch64 или e2k, в остальных случаях - [V8Arch::Unknown];
+    /// * macOS - по полю `cputype` Mach-O заголовка 1cv8s, 1cv8, 1cv8c, ragent, 1cv8t, 1cv8ct или исполняемого
+    ///   файла внутри 1cv8.app. Для универсальных (fat) файлов выбирается архитектура текущего компьютера, если она
+    ///   есть в файле. Если прочитать файлы не удалось - [V8Arch::Unknown].
     ///
-    /// Если по каким-то причинам метод не сможет определить разрядность самостоятельно,
-    /// по умолчанию присваивается разрядность 32
-    pub fn from_path(v8_path: &PathBuf) -> V8Arch {
-        let current_os = env::consts::OS;
-        return match current_os {
-            "windows" => V8Arch::v8_arch_from_exe(&v8_path.join(get_v8s_suffix())),
-            "linux" => V8Arch::v8_arch_from_linux_path(v8_path),
-            _ => V8Arch::X64
+    /// В остальных ОС архитектура не определяется и также считается [V8Arch::Unknown]. Способ определения
+    /// можно узнать через [V8Arch::detect_from_path]
+    pub fn from_path(v8_path: &Path) -> V8Arch {
+        V8Arch::detect_from_path(v8_path).0
+    }
+
+    /// Аналог [V8Arch::from_path], дополнительно возвращающий способ определения архитектуры
+    pub fn detect_from_path(v8_path: &Path) -> (V8Arch, V8ArchSource) {
+        V8Arch::detect(&SystemEnvironment, v8_path)
+    }
+
+    /// Архитектура компьютера, на котором выполняется поиск. `None`, если 1С под такую архитектуру
+    /// не выпускается
+    pub fn native() -> Option<V8Arch> {
+        V8Arch::from_rust_arch(SystemEnvironment.arch())
+    }
+
+    // Имя архитектуры в формате std::env::consts::ARCH
+    pub(crate) fn from_rust_arch(arch: &str) -> Option<V8Arch> {
+        match arch {
+            "x86" => Some(V8Arch::X86),
+            "x86_64" => Some(V8Arch::X64),
+            "aarch64" => Some(V8Arch::Arm64),
+            "e2k" | "e2k64" => Some(V8Arch::E2k),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn detect(env: &dyn Environment, v8_path: &Path) -> (V8Arch, V8ArchSource) {
+        match env.os() {
+            "windows" => V8Arch::v8_arch_from_binaries(env, v8_path, WINDOWS_BINARIES),
+            "linux" => V8Arch::v8_arch_from_binaries(env, v8_path, LINUX_BINARIES)
+                .or_else(|| V8Arch::v8_arch_from_linux_path(v8_path)),
+            "macos" => V8Arch::v8_arch_from_binaries(env, v8_path, MACOS_BINARIES),
+            _ => None,
+        }.unwrap_or((V8Arch::Unknown, V8ArchSource::Default))
+    }
+
+    fn v8_arch_from_binaries(env: &dyn Environment, v8_path: &Path,
+                             binaries: &[&str]) -> Option<(V8Arch, V8ArchSource)> {
+        binaries.iter()
+            .map(|binary| v8_path.join(binary))
+            .filter(|binary_path| env.exists(binary_path))
+            .find_map(|binary_path| match V8Arch::read_binary_arch(env, &binary_path) {
+                Ok(detected) => Some(detected),
+                Err(err) => {
+                    warn!("{}", err);
+                    None
+                }
+            })
+    }
+
+    fn read_binary_arch(env: &dyn Environment, path_to_binary: &Path) -> Result<(V8Arch, V8ArchSource), V8FindError> {
+        let read_prefix = |len| env.read_prefix(path_to_binary, len)
+            .map_err(|source| V8FindError::Io { path: path_to_binary.to_owned(), source });
+        let mut buf = read_prefix(v8_binary::HEADER_LEN)?;
+
+        if v8_binary::is_pe(&buf) {
+            if let Some(pe_header_len) = v8_binary::pe_header_len(&buf).filter(|len| *len > buf.len()) {
+                buf = read_prefix(pe_header_len)?;
+            }
+
+            return v8_binary::arch_from_pe(&buf)
+                .map(|arch| (arch, V8ArchSource::PeHeader))
+                .map_err(|message| V8FindError::PeParse { path: path_to_binary.to_owned(), message });
+        }
+
+        if v8_binary::is_macho(&buf) {
+            let native_arch = V8Arch::from_rust_arch(env.arch());
+            return v8_binary::arch_from_macho(&buf, native_arch)
+                .map(|arch| (arch, V8ArchSource::MachOHeader))
+                .map_err(|message| V8FindError::MachOParse { path: path_to_binary.to_owned(), message });
         }
+
+        v8_binary::arch_from_elf(&buf)
+            .map(|arch| (arch, V8ArchSource::ElfHeader))
+            .map_err(|message| V8FindError::ElfParse { path: path_to_binary.to_owned(), message })
     }
 
-    fn v8_arch_from_linux_path(path: &PathBuf) -> V8Arch {
+    fn v8_arch_from_linux_path(path: &Path) -> Option<(V8Arch, V8ArchSource)> {
         let str_path = path.to_str().unwrap_or("");
-        if str_path.contains("i386") {
+        let arch = if str_path.contains("i386") {
             V8Arch::X86
-        } else {
+        } else if str_path.contains("x86_64") {
             V8Arch::X64
+        } else if str_path.contains("aarch64") || str_path.contains("arm64") {
+            V8Arch::Arm64
+        } else if str_path.contains("e2k") {
+            V8Arch::E2k
+        } else {
+            return None;
+        };
+
+        Some((arch, V8ArchSource::PathHeuristic))
+    }
+
+    /// Определяет архитектуру по PE заголовку исполняемого файла. В отличие от [V8Arch::from_path]
+    /// возвращает ошибку, если файл не удалось прочитать или разобрать, а также если тип машины
+    /// не поддерживается
+    pub fn try_from_exe(path_to_exe: &Path) -> Result<V8Arch, V8FindError> {
+        match V8Arch::read_binary_arch(&SystemEnvironment, path_to_exe)? {
+            (v8_arch, V8ArchSource::PeHeader) => Ok(v8_arch),
+            _ => Err(V8FindError::PeParse { path: path_to_exe.to_owned(), message: "not a PE file".to_owned() }),
         }
     }
+}
 
-    fn v8_arch_from_exe(path_to_exe: &PathBuf) -> V8Arch {
-        if path_to_exe.exists() {
-            let exe_file = File::open(path_to_exe);
-            if let Ok(mut exe_file) = exe_file {
-                let mut buf = Vec::new();
-                let _ = exe_file.read_to_end(&mut buf);
-
-                let pe_file = PE::new_disk(buf.as_slice());
-                let v8_arch = pe_file.get_arch();
-                return match v8_arch {
-                    Ok(Arch::X64) => V8Arch::X64,
-                    Ok(Arch::X86) => V8Arch::X86,
-                    Err(_) => V8Arch::X86,
-                };
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::v8_app::v8_binary::tests::{elf, fat, pe};
+    use crate::v8_env::MemoryEnvironment;
+
+    #[test]
+    fn linux_arch_from_elf_header_before_path() {
+        let env = MemoryEnvironment::new("linux")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/1cv8", elf(1, 1, 3));
+        let detected = V8Arch::detect(&env, Path::new("/opt/1cv8/x86_64/8.3.22.1709"));
+        assert_eq!(detected, (V8Arch::X86, V8ArchSource::ElfHeader));
+    }
+
+    #[test]
+    fn linux_arch_from_path_when_header_unreadable() {
+        let env = MemoryEnvironment::new("linux")
+            .with_file("/opt/1cv8/aarch64/8.3.22.1709/1cv8", "#!/bin/sh\n")
+            .with_dir("/opt/1cv8/e2k/8.3.22.1709");
+        assert_eq!(V8Arch::detect(&env, Path::new("/opt/1cv8/aarch64/8.3.22.1709")),
+                   (V8Arch::Arm64, V8ArchSource::PathHeuristic));
+        assert_eq!(V8Arch::detect(&env, Path::new("/opt/1cv8/e2k/8.3.22.1709")),
+                   (V8Arch::E2k, V8ArchSource::PathHeuristic));
+    }
+
+    #[test]
+    fn windows_arch_from_pe_header() {
+        let env = MemoryEnvironment::new("windows")
+            .with_file(r"C:\Program Files\1cv8\8.3.22.1709\bin\1cv8.exe", pe(0xaa64, 0x80));
+        assert_eq!(V8Arch::detect(&env, Path::new(r"C:\Program Files\1cv8\8.3.22.1709")),
+                   (V8Arch::Arm64, V8ArchSource::PeHeader));
+    }
+
+    #[test]
+    fn macos_universal_binary_uses_native_arch() {
+        let universal = fat(&[0x0100_0007, 0x0100_000c], false);
+        let v8_path = Path::new("/opt/1cv8/8.3.22.1709");
+        for (native, expected) in [("aarch64", V8Arch::Arm64), ("x86_64", V8Arch::X64)] {
+            let env = MemoryEnvironment::new("macos")
+                .with_arch(native)
+                .with_file("/opt/1cv8/8.3.22.1709/1cv8.app/Contents/MacOS/1cv8", universal.clone());
+            assert_eq!(V8Arch::detect(&env, v8_path), (expected, V8ArchSource::MachOHeader));
         }
+    }
 
-        V8Arch::X86
+    #[test]
+    fn unknown_arch_when_nothing_is_readable() {
+        let cases = [
+            ("windows", r"C:\Program Files\1cv8\8.3.22.1709"),
+            ("linux", "/srv/1c/8.3.22.1709"),
+            ("macos", "/opt/1cv8/8.3.22.1709"),
+            ("freebsd", "/usr/local/1cv8/8.3.22.1709"),
+        ];
+        for (os, v8_path) in cases {
+            let env = MemoryEnvironment::new(os).with_dir(v8_path);
+            assert_eq!(V8Arch::detect(&env, Path::new(v8_path)), (V8Arch::Unknown, V8ArchSource::Default), "{}", os);
+        }
     }
-}
 
-fn get_v8s_suffix() -> &'static str {
-    let current_os = env::consts::OS;
-    match current_os {
-        "windows" => r"bin\1cv8s.exe",
-        _ => "1cv8s",
+    fn components(env: &MemoryEnvironment, v8_path: &str) -> Vec<V8Component> {
+        V8Component::detect(env, Path::new(v8_path)).iter().collect()
+    }
+
+    #[test]
+    fn windows_components_follow_app_layout() {
+        let v8_path = r"C:\Program Files\1cv8\8.3.22.1709";
+        let env = MemoryEnvironment::new("windows")
+            .with_file(format!(r"{}\bin\1CV8.EXE", v8_path), "")
+            .with_file(format!(r"{}\bin\ragent.exe", v8_path), "")
+            .with_file(format!(r"{}\bin\rmngr.exe", v8_path), "")
+            .with_file(format!(r"{}\bin\wsisapi.dll", v8_path), "")
+            .with_file(format!(r"{}\bin\wsap24.dll", v8_path), "")
+            .with_file(format!(r"{}\webinst.exe", v8_path), "")
+            .with_file(format!(r"{}\bin\cnvdbfl.exe", v8_path), "");
+
+        // Без rphost сервер не считается установленным
+        assert_eq!(components(&env, v8_path), [V8Component::ThickClient, V8Component::ApacheModule,
+            V8Component::IISModule, V8Component::WebInst, V8Component::DbFileTools]);
+    }
+
+    #[test]
+    fn components_agree_with_get_app_by_type() {
+        use crate::v8_finder::V8Finder;
+
+        let env = MemoryEnvironment::new("windows")
+            .with_file(r"C:\1c\8.3.22.1709\bin\1cv8c.exe", "")
+            .with_file(r"C:\1c\8.3.22.1709\webinst.exe", "");
+        let finder = V8Finder::builder().no_defaults().root(r"C:\1c").environment(env).build();
+        let platform = &finder.platforms()[0];
+
+        assert!(platform.get_app_by_type(V8AppType::WebInst).is_some());
+        assert!(platform.has_component(V8Component::WebInst));
+        assert!(platform.get_app_by_type(V8AppType::ThinClient).is_some());
+        assert!(platform.has_component(V8Component::ThinClient));
+        assert!(platform.get_app_by_type(V8AppType::ThickClient).is_none());
+        assert!(!platform.has_component(V8Component::ThickClient));
+    }
+
+    #[test]
+    fn macos_components_in_app_bundles() {
+        let v8_path = "/opt/1cv8/8.3.22.1709";
+        let env = MemoryEnvironment::new("macos")
+            .with_file("/opt/1cv8/8.3.22.1709/1cv8.app/Contents/MacOS/1cv8", "")
+            .with_file("/opt/1cv8/8.3.22.1709/1cv8c.app/Contents/Info.plist", "")
+            .with_file("/opt/1cv8/8.3.22.1709/ibcmd", "");
+
+        // В 1cv8c.app нет исполняемого файла
+        assert_eq!(components(&env, v8_path), [V8Component::ThickClient, V8Component::IBCMD]);
+    }
+
+    #[test]
+    fn linux_server_and_modules() {
+        let v8_path = "/opt/1cv8/x86_64/8.3.22.1709";
+        let env = MemoryEnvironment::new("linux")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/ragent", "")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/rmngr", "")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/rphost", "")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/ras", "")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/wsap22.so", "");
+
+        let detected = V8Component::detect(&env, Path::new(v8_path));
+        assert_eq!(detected.iter().collect::<Vec<_>>(),
+                   [V8Component::Server, V8Component::RAS, V8Component::ApacheModule]);
+        assert!(detected.is_server_only());
+    }
+
+    #[test]
+    fn linux_file_names_are_case_sensitive() {
+        use crate::v8_finder::V8Finder;
+
+        let env = MemoryEnvironment::new("linux")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/1CV8", "")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/1cv8c", "")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/Ibcmd", "");
+        assert_eq!(components(&env, "/opt/1cv8/x86_64/8.3.22.1709"), [V8Component::ThinClient]);
+
+        let finder = V8Finder::builder().no_defaults().root("/opt/1cv8/x86_64").environment(env).build();
+        let platform = &finder.platforms()[0];
+        assert!(platform.get_app_by_type(V8AppType::ThickClient).is_none());
+        assert!(!platform.has_component(V8Component::ThickClient));
+        assert!(platform.get_app_by_type(V8AppType::ThinClient).is_some());
+        assert!(platform.has_component(V8Component::ThinClient));
+    }
+
+    #[test]
+    fn editions() {
+        let detect = |components: &[V8Component], v8_path: &str| {
+            V8Edition::detect(&components.iter().copied().collect(), Path::new(v8_path))
+        };
+        let v8_path = "/opt/1cv8/x86_64/8.3.22.1709";
+
+        assert_eq!(detect(&[V8Component::ThickClient, V8Component::Server], v8_path), V8Edition::Full);
+        assert_eq!(detect(&[V8Component::ThinClient], v8_path), V8Edition::ThinClientOnly);
+        assert_eq!(detect(&[V8Component::Server, V8Component::RAS], v8_path), V8Edition::ServerOnly);
+        assert_eq!(detect(&[V8Component::TrainingClient], v8_path), V8Edition::Training);
+        assert_eq!(detect(&[V8Component::ThickClient], "/opt/1cv8t/x86_64/8.3.22.1709"), V8Edition::Training);
+        // Утилиты без клиентов и сервера - все еще установленная платформа
+        assert_eq!(detect(&[V8Component::IBCMD], v8_path), V8Edition::Full);
+        assert_eq!(detect(&[], v8_path), V8Edition::Unknown);
+        assert_eq!(detect(&[], "/opt/1cv8t/x86_64/8.3.22.1709"), V8Edition::Unknown);
     }
 }
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use crate::v8_app::V8AppType;
+
+// Шаблоны путей относительно каталога версии платформы. `{name}` заменяется на имя приложения,
+// `..` позволяет выйти за пределы каталога версии
+const DEFAULT_LOCATIONS: &[(&str, &[&str])] = &[
+    ("windows", &["bin/{name}.exe"]),
+    ("linux", &["{name}"]),
+    ("macos", &["{name}", "{name}.app/Contents/MacOS/{name}"]),
+];
+
+// Приложения, расположенные не там, где остальные. Проверяются перед расположением по умолчанию
+const APP_LOCATIONS: &[(&str, &str, &[&str])] = &[
+    ("1cestart", "windows", &["../common/1cestart.exe"]),
+    ("1cestart", "linux", &["../../common/1cestart", "../common/1cestart"]),
+    ("1cestart", "macos", &["../1cestart", "../1cestart.app/Contents/MacOS/1cestart"]),
+    ("webinst", "windows", &["bin/webinst.exe", "webinst.exe"]),
+];
+
+// Модули расширения веб-серверов. Не являются приложениями, но ищутся так же, например,
+// через V8AppType::Custom("wsisapi"), и входят в состав компонентов платформы
+const MODULES: &[&str] = &["wsap24", "wsap22", "wsap20", "wsisapi"];
+const MODULE_LOCATIONS: &[(&str, &[&str])] = &[
+    ("windows", &["bin/{name}.dll"]),
+    ("linux", &["{name}.so"]),
+    ("macos", &["{name}.so"]),
+];
+
+/// Описание расположения исполняемых файлов приложений в каталоге версии платформы для каждой ОС.
+/// По умолчанию в Windows это `bin\имя.exe`, в Linux - `имя`, в macOS - `имя` или `имя.app`, для модулей
+/// веб-серверов wsap24, wsap22, wsap20 и wsisapi - `bin\имя.dll` в Windows и `имя.so` в остальных ОС.
+/// Стандартное расположение используется и для определения состава компонентов платформы. Расположение
+/// можно дополнить для отдельных приложений, в том числе [V8AppType::Custom].
+///```no_run
+/// use v8find4rs::v8_app::{V8AppLayout, V8AppType};
+/// use v8find4rs::v8_finder::{SearchPriority, V8Finder};
+///
+/// let layout = V8AppLayout::new()
+///     .with_app(&V8AppType::Custom("mytool".to_owned()), "windows", &["tools/{name}.exe"]);
+/// let finder = V8Finder::new();
+/// let platform = finder.get_platform("8.3", SearchPriority::X64).unwrap();
+/// let tool = platform.get_app(&V8AppType::Custom("mytool".to_owned()), &layout);
+/// ```
+#[derive(Debug, Clone)]
+pub struct V8AppLayout {
+    defaults: HashMap<String, Vec<String>>,
+    apps: HashMap<(String, String), Vec<String>>,
+}
+
+impl V8AppLayout {
+    /// Расположение, используемое [V8Platform::get_app_by_type](crate::v8_finder::V8Platform::get_app_by_type)
+    pub fn new() -> Self {
+        let mut layout = V8AppLayout { defaults: HashMap::new(), apps: HashMap::new() };
+        for (os, templates) in DEFAULT_LOCATIONS {
+            layout = layout.with_default(os, templates);
+        }
+        for (name, os, templates) in APP_LOCATIONS {
+            layout.apps.insert((name.to_string(), os.to_string()), to_owned_templates(templates));
+        }
+        for name in MODULES {
+            for (os, templates) in MODULE_LOCATIONS {
+                layout.apps.insert((name.to_string(), os.to_string()), to_owned_templates(templates));
+            }
+        }
+        layout
+    }
+
+    /// Заменяет расположение по умолчанию для ОС. Шаблоны задаются относительно каталога версии,
+    /// `{name}` заменяется на [V8AppType::value], разделителем служит `/`
+    pub fn with_default(mut self, os: &str, templates: &[&str]) -> Self {
+        self.defaults.insert(os.to_owned(), to_owned_templates(templates));
+        self
+    }
+
+    /// Задает расположение приложения для ОС. Эти шаблоны проверяются раньше расположения по умолчанию
+    pub fn with_app(mut self, app_type: &V8AppType, os: &str, templates: &[&str]) -> Self {
+        self.apps.insert((app_type.value().to_owned(), os.to_owned()), to_owned_templates(templates));
+        self
+    }
+
+    /// Возможные пути к файлу приложения в порядке проверки
+    pub fn candidates(&self, app_type: &V8AppType, os: &str, v8_path: &Path) -> Vec<PathBuf> {
+        self.candidates_by_name(app_type.value(), os, v8_path)
+    }
+
+    // Аналог candidates по имени файла приложения или модуля без расширения, см. V8AppType::value
+    pub(crate) fn candidates_by_name(&self, name: &str, os: &str, v8_path: &Path) -> Vec<PathBuf> {
+        let app_templates = self.apps.get(&(name.to_owned(), os.to_owned()));
+        let default_templates = self.defaults.get(os);
+
+        app_templates.into_iter()
+            .chain(default_templates)
+            .flatten()
+            .map(|template| resolve(v8_path, &template.replace("{name}", name)))
+            .collect()
+    }
+}
+
+impl Default for V8AppLayout {
+    fn default() -> Self {
+        V8AppLayout::new()
+    }
+}
+
+fn to_owned_templates(templates: &[&str]) -> Vec<String> {
+    templates.iter().map(|template| template.to_string()).collect()
+}
+
+fn resolve(v8_path: &Path, relative: &str) -> PathBuf {
+    let mut path = v8_path.to_path_buf();
+    for part in relative.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if !path.pop() {
+                    path.push(Component::ParentDir);
+                }
+            }
+            part => path.push(part),
+        }
+    }
+    path
+}
@@ -0,0 +1,388 @@
+use exe::{VSVersionInfo, PE};
+
+use crate::v8_app::V8Arch;
+use crate::v8_version::V8Version;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELF_CLASS_32: u8 = 1;
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LSB: u8 = 1;
+const ELF_DATA_MSB: u8 = 2;
+
+const EM_386: u16 = 3;
+const EM_X86_64: u16 = 62;
+const EM_MCST_ELBRUS: u16 = 175;
+const EM_AARCH64: u16 = 183;
+
+const PE_MAGIC: &[u8] = b"MZ";
+const PE_SIGNATURE: &[u8] = b"PE\0\0";
+const PE_HEADER_OFFSET: usize = 0x3c;
+const PE_MAX_HEADER_LEN: usize = 0x10_0000;
+
+const IMAGE_FILE_MACHINE_I386: u16 = 0x14c;
+const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+const IMAGE_FILE_MACHINE_ARM64: u16 = 0xaa64;
+
+const MACHO_MAGIC_32: u32 = 0xfeedface;
+const MACHO_MAGIC_64: u32 = 0xfeedfacf;
+const FAT_MAGIC: u32 = 0xcafebabe;
+const FAT_MAGIC_64: u32 = 0xcafebabf;
+
+const CPU_TYPE_X86: u32 = 7;
+const CPU_TYPE_X86_64: u32 = 0x0100_0007;
+const CPU_TYPE_ARM64: u32 = 0x0100_000c;
+
+/// Сколько первых байт файла читается для определения архитектуры. Для ELF и Mach-O этого
+/// достаточно всегда, для PE - если заголовок не вынесен дальше обычного
+pub(crate) const HEADER_LEN: usize = 4096;
+
+/// Определяет архитектуру по заголовку ELF: классу из `e_ident` и полю `e_machine`
+pub(crate) fn arch_from_elf(header: &[u8]) -> Result<V8Arch, String> {
+    if header.len() < 20 || !header.starts_with(ELF_MAGIC) {
+        return Err("not an ELF file".to_owned());
+    }
+
+    let class = header[4];
+    let machine_bytes = [header[18], header[19]];
+    let machine = match header[5] {
+        ELF_DATA_LSB => u16::from_le_bytes(machine_bytes),
+        ELF_DATA_MSB => u16::from_be_bytes(machine_bytes),
+        data => return Err(format!("unknown ELF data encoding {}", data)),
+    };
+
+    match (class, machine) {
+        (ELF_CLASS_32, EM_386) => Ok(V8Arch::X86),
+        (ELF_CLASS_64, EM_X86_64) => Ok(V8Arch::X64),
+        (ELF_CLASS_64, EM_AARCH64) => Ok(V8Arch::Arm64),
+        (_, EM_MCST_ELBRUS) => Ok(V8Arch::E2k),
+        (class, machine) => Err(format!("unsupported ELF class {} and machine {}", class, machine)),
+    }
+}
+
+/// Определяет архитектуру по полю `Machine` COFF заголовка PE файла. Неизвестный тип машины
+/// считается ошибкой, а не 32-х разрядной платформой
+pub(crate) fn arch_from_pe(header: &[u8]) -> Result<V8Arch, String> {
+    if !header.starts_with(PE_MAGIC) {
+        return Err("not a PE file".to_owned());
+    }
+
+    let pe_offset = read_u32_le(header, PE_HEADER_OFFSET)
+        .ok_or_else(|| "truncated DOS header".to_owned())? as usize;
+    let signature = header.get(pe_offset..pe_offset + PE_SIGNATURE.len());
+    if signature != Some(PE_SIGNATURE) {
+        return Err("PE signature not found".to_owned());
+    }
+
+    let machine = header.get(pe_offset + 4..pe_offset + 6)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| "truncated COFF header".to_owned())?;
+
+    match machine {
+        IMAGE_FILE_MACHINE_I386 => Ok(V8Arch::X86),
+        IMAGE_FILE_MACHINE_AMD64 => Ok(V8Arch::X64),
+        IMAGE_FILE_MACHINE_ARM64 => Ok(V8Arch::Arm64),
+        machine => Err(format!("unsupported PE machine {:#x}", machine)),
+    }
+}
+
+/// Проверяет, начинается ли файл с DOS заголовка PE файла
+pub(crate) fn is_pe(header: &[u8]) -> bool {
+    header.starts_with(PE_MAGIC)
+}
+
+/// Сколько первых байт PE файла нужно прочитать, чтобы получить поле `Machine`. `None`, если
+/// прочитанного недостаточно даже для смещения PE заголовка или смещение неправдоподобно велико
+pub(crate) fn pe_header_len(header: &[u8]) -> Option<usize> {
+    read_u32_le(header, PE_HEADER_OFFSET)
+        .map(|pe_offset| pe_offset as usize + 6)
+        .filter(|len| *len <= PE_MAX_HEADER_LEN)
+}
+
+/// Проверяет, является ли файл Mach-O, в том числе универсальным (fat)
+pub(crate) fn is_macho(header: &[u8]) -> bool {
+    let magic_le = read_u32_le(header, 0);
+    let magic_be = read_u32_be(header, 0);
+    matches!(magic_le, Some(MACHO_MAGIC_32) | Some(MACHO_MAGIC_64))
+        || matches!(magic_be, Some(FAT_MAGIC) | Some(FAT_MAGIC_64))
+}
+
+/// Определяет архитектуру по полю `cputype` заголовка Mach-O. Для универсальных файлов выбирается
+/// `preferred`, если такая архитектура в файле есть, иначе первая известная
+pub(crate) fn arch_from_macho(header: &[u8], preferred: Option<V8Arch>) -> Result<V8Arch, String> {
+    match read_u32_be(header, 0) {
+        Some(FAT_MAGIC) => return arch_from_fat(header, 20, preferred),
+        Some(FAT_MAGIC_64) => return arch_from_fat(header, 32, preferred),
+        _ => {}
+    }
+
+    match read_u32_le(header, 0) {
+        Some(MACHO_MAGIC_32) | Some(MACHO_MAGIC_64) => {
+            let cpu_type = read_u32_le(header, 4).ok_or_else(|| "truncated Mach-O header".to_owned())?;
+            arch_from_cpu_type(cpu_type).ok_or_else(|| format!("unsupported Mach-O cputype {:#x}", cpu_type))
+        }
+        _ => Err("not a Mach-O file".to_owned()),
+    }
+}
+
+fn arch_from_fat(header: &[u8], entry_size: usize, preferred: Option<V8Arch>) -> Result<V8Arch, String> {
+    let count = read_u32_be(header, 4).ok_or_else(|| "truncated fat header".to_owned())? as usize;
+    let mut archs = Vec::with_capacity(count.min(16));
+    for index in 0..count {
+        let cpu_type = read_u32_be(header, 8 + index * entry_size)
+            .ok_or_else(|| "truncated fat header".to_owned())?;
+        archs.extend(arch_from_cpu_type(cpu_type));
+    }
+
+    match preferred {
+        Some(preferred) if archs.contains(&preferred) => Ok(preferred),
+        _ => archs.first().copied().ok_or_else(|| "no supported architectures in fat binary".to_owned()),
+    }
+}
+
+fn arch_from_cpu_type(cpu_type: u32) -> Option<V8Arch> {
+    match cpu_type {
+        CPU_TYPE_X86 => Some(V8Arch::X86),
+        CPU_TYPE_X86_64 => Some(V8Arch::X64),
+        CPU_TYPE_ARM64 => Some(V8Arch::Arm64),
+        _ => None,
+    }
+}
+
+fn read_u32_le(buf: &[u8], offset: usize) -> Option<u32> {
+    buf.get(offset..offset + 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u32_be(buf: &[u8], offset: usize) -> Option<u32> {
+    buf.get(offset..offset + 4).map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Читает версию файла из ресурса VS_VERSIONINFO (поле `FileVersion` структуры VS_FIXEDFILEINFO).
+/// В отличие от остальных функций модуля требует PE файл целиком
+pub(crate) fn version_from_pe(data: &[u8]) -> Result<V8Version, String> {
+    let pe = PE::new_disk(data);
+    let version_info = VSVersionInfo::parse(&pe).map_err(|err| err.to_string())?;
+    let fixed_info = version_info.value.ok_or_else(|| "VS_VERSIONINFO has no fixed file info".to_owned())?;
+
+    Ok(V8Version::new(
+        fixed_info.file_version_ms >> 16,
+        fixed_info.file_version_ms & 0xffff,
+        fixed_info.file_version_ls >> 16,
+        fixed_info.file_version_ls & 0xffff,
+    ))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Минимальный заголовок ELF с заданными классом, порядком байт и типом машины
+    pub(crate) fn elf(class: u8, data: u8, machine: u16) -> Vec<u8> {
+        let mut header = vec![0u8; 64];
+        header[..4].copy_from_slice(ELF_MAGIC);
+        header[4] = class;
+        header[5] = data;
+        let machine_bytes = if data == ELF_DATA_MSB { machine.to_be_bytes() } else { machine.to_le_bytes() };
+        header[18..20].copy_from_slice(&machine_bytes);
+        header
+    }
+
+    /// Минимальный PE файл: DOS заголовок со смещением PE заголовка `pe_offset` и поле `Machine`
+    pub(crate) fn pe(machine: u16, pe_offset: usize) -> Vec<u8> {
+        let mut header = vec![0u8; pe_offset + 24];
+        header[..2].copy_from_slice(PE_MAGIC);
+        header[PE_HEADER_OFFSET..PE_HEADER_OFFSET + 4].copy_from_slice(&(pe_offset as u32).to_le_bytes());
+        header[pe_offset..pe_offset + 4].copy_from_slice(PE_SIGNATURE);
+        header[pe_offset + 4..pe_offset + 6].copy_from_slice(&machine.to_le_bytes());
+        header
+    }
+
+    /// Минимальный 32-х разрядный PE файл с единственным ресурсом VS_VERSIONINFO, в котором задана только
+    /// структура VS_FIXEDFILEINFO с версией файла `file_version`
+    pub(crate) fn pe_with_version(file_version: [u16; 4]) -> Vec<u8> {
+        const PE_OFFSET: usize = 0x80;
+        const OPTIONAL_HEADER_LEN: usize = 224;
+        const SECTION_RVA: u32 = 0x1000;
+        const SECTION_OFFSET: usize = 0x200;
+        const VERSION_INFO: usize = 0x58;
+        const VERSION_INFO_LEN: usize = 0x5c;
+
+        let mut data = pe(IMAGE_FILE_MACHINE_I386, PE_OFFSET);
+        data.resize(SECTION_OFFSET + 0x200, 0);
+        let mut put = |offset: usize, bytes: &[u8]| data[offset..offset + bytes.len()].copy_from_slice(bytes);
+
+        // Заголовок COFF: одна секция и необязательный заголовок PE32
+        put(PE_OFFSET + 6, &1u16.to_le_bytes());
+        put(PE_OFFSET + 20, &(OPTIONAL_HEADER_LEN as u16).to_le_bytes());
+        let optional_header = PE_OFFSET + 24;
+        put(optional_header, &0x10bu16.to_le_bytes());
+        put(optional_header + 32, &0x1000u32.to_le_bytes());
+        put(optional_header + 36, &0x200u32.to_le_bytes());
+        put(optional_header + 56, &0x2000u32.to_le_bytes());
+        put(optional_header + 60, &(SECTION_OFFSET as u32).to_le_bytes());
+        put(optional_header + 92, &16u32.to_le_bytes());
+        // Каталог ресурсов - третий элемент DataDirectory
+        put(optional_header + 112, &SECTION_RVA.to_le_bytes());
+        put(optional_header + 116, &0x200u32.to_le_bytes());
+
+        let section = optional_header + OPTIONAL_HEADER_LEN;
+        put(section, b".rsrc\0\0\0");
+        put(section + 8, &0x200u32.to_le_bytes());
+        put(section + 12, &SECTION_RVA.to_le_bytes());
+        put(section + 16, &0x200u32.to_le_bytes());
+        put(section + 20, &(SECTION_OFFSET as u32).to_le_bytes());
+
+        // Дерево ресурсов: тип RT_VERSION (16), идентификатор 1, язык 0x409
+        for (level, (id, target)) in [(16u32, 0x8000_0018u32), (1, 0x8000_0030), (0x409, 0x48)].iter().enumerate() {
+            let directory = SECTION_OFFSET + level * 0x18;
+            put(directory + 14, &1u16.to_le_bytes());
+            put(directory + 16, &id.to_le_bytes());
+            put(directory + 20, &target.to_le_bytes());
+        }
+        put(SECTION_OFFSET + 0x48, &(SECTION_RVA + VERSION_INFO as u32).to_le_bytes());
+        put(SECTION_OFFSET + 0x4c, &(VERSION_INFO_LEN as u32).to_le_bytes());
+
+        let version_info = SECTION_OFFSET + VERSION_INFO;
+        put(version_info, &(VERSION_INFO_LEN as u16).to_le_bytes());
+        put(version_info + 2, &52u16.to_le_bytes());
+        let key: Vec<u8> = "VS_VERSION_INFO\0".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        put(version_info + 6, &key);
+        let fixed_info = version_info + 0x28;
+        put(fixed_info, &0xfeef_04bdu32.to_le_bytes());
+        put(fixed_info + 4, &0x0001_0000u32.to_le_bytes());
+        let [major, minor, release, build] = file_version;
+        put(fixed_info + 8, &(u32::from(major) << 16 | u32::from(minor)).to_le_bytes());
+        put(fixed_info + 12, &(u32::from(release) << 16 | u32::from(build)).to_le_bytes());
+        data
+    }
+
+    /// Минимальный заголовок 64-х разрядного Mach-O с заданным `cputype`
+    pub(crate) fn macho(cpu_type: u32) -> Vec<u8> {
+        let mut header = vec![0u8; 32];
+        header[..4].copy_from_slice(&MACHO_MAGIC_64.to_le_bytes());
+        header[4..8].copy_from_slice(&cpu_type.to_le_bytes());
+        header
+    }
+
+    /// Минимальный заголовок универсального Mach-O с записями `fat_arch` (или `fat_arch_64`) заданных типов
+    pub(crate) fn fat(cpu_types: &[u32], is_64: bool) -> Vec<u8> {
+        let (magic, entry_size) = if is_64 { (FAT_MAGIC_64, 32) } else { (FAT_MAGIC, 20) };
+        let mut header = Vec::new();
+        header.extend_from_slice(&magic.to_be_bytes());
+        header.extend_from_slice(&(cpu_types.len() as u32).to_be_bytes());
+        for cpu_type in cpu_types {
+            let mut entry = vec![0u8; entry_size];
+            entry[..4].copy_from_slice(&cpu_type.to_be_bytes());
+            header.extend(entry);
+        }
+        header
+    }
+
+    #[test]
+    fn elf_architectures() {
+        assert_eq!(arch_from_elf(&elf(ELF_CLASS_32, ELF_DATA_LSB, EM_386)), Ok(V8Arch::X86));
+        assert_eq!(arch_from_elf(&elf(ELF_CLASS_64, ELF_DATA_LSB, EM_X86_64)), Ok(V8Arch::X64));
+        assert_eq!(arch_from_elf(&elf(ELF_CLASS_64, ELF_DATA_LSB, EM_AARCH64)), Ok(V8Arch::Arm64));
+        assert_eq!(arch_from_elf(&elf(ELF_CLASS_64, ELF_DATA_MSB, EM_X86_64)), Ok(V8Arch::X64));
+    }
+
+    #[test]
+    fn rejects_unsupported_elf() {
+        assert!(arch_from_elf(&elf(ELF_CLASS_64, ELF_DATA_LSB, EM_386)).is_err());
+        assert!(arch_from_elf(&elf(ELF_CLASS_32, ELF_DATA_LSB, EM_X86_64)).is_err());
+        assert!(arch_from_elf(&elf(ELF_CLASS_64, 3, EM_X86_64)).is_err());
+        assert!(arch_from_elf(&elf(ELF_CLASS_64, ELF_DATA_LSB, EM_X86_64)[..19]).is_err());
+        assert!(arch_from_elf(b"#!/bin/sh\nexec 1cv8 \"$@\"\n").is_err());
+    }
+
+    #[test]
+    fn elbrus_elf_of_any_class() {
+        assert_eq!(arch_from_elf(&elf(ELF_CLASS_64, ELF_DATA_LSB, EM_MCST_ELBRUS)), Ok(V8Arch::E2k));
+        assert_eq!(arch_from_elf(&elf(ELF_CLASS_32, ELF_DATA_LSB, EM_MCST_ELBRUS)), Ok(V8Arch::E2k));
+    }
+
+    #[test]
+    fn pe_architectures() {
+        assert_eq!(arch_from_pe(&pe(IMAGE_FILE_MACHINE_I386, 0x80)), Ok(V8Arch::X86));
+        assert_eq!(arch_from_pe(&pe(IMAGE_FILE_MACHINE_AMD64, 0x80)), Ok(V8Arch::X64));
+        assert_eq!(arch_from_pe(&pe(IMAGE_FILE_MACHINE_ARM64, 0x80)), Ok(V8Arch::Arm64));
+        assert!(is_pe(&pe(IMAGE_FILE_MACHINE_AMD64, 0x80)));
+    }
+
+    #[test]
+    fn rejects_invalid_pe() {
+        assert!(arch_from_pe(&pe(0x1c0, 0x80)).is_err());
+        assert!(arch_from_pe(&elf(ELF_CLASS_64, ELF_DATA_LSB, EM_X86_64)).is_err());
+        assert!(arch_from_pe(&b"MZ"[..]).is_err());
+
+        let mut bad_signature = pe(IMAGE_FILE_MACHINE_AMD64, 0x80);
+        bad_signature[0x80] = b'N';
+        assert_eq!(arch_from_pe(&bad_signature), Err("PE signature not found".to_owned()));
+    }
+
+    #[test]
+    fn pe_header_beyond_first_read() {
+        let header = pe(IMAGE_FILE_MACHINE_AMD64, HEADER_LEN + 0x100);
+        assert_eq!(pe_header_len(&header[..HEADER_LEN]), Some(HEADER_LEN + 0x106));
+        assert!(arch_from_pe(&header[..HEADER_LEN]).is_err());
+        assert_eq!(arch_from_pe(&header), Ok(V8Arch::X64));
+
+        let mut implausible = pe(IMAGE_FILE_MACHINE_AMD64, 0x80);
+        implausible[PE_HEADER_OFFSET..PE_HEADER_OFFSET + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(pe_header_len(&implausible), None);
+        assert_eq!(pe_header_len(&b"MZ"[..]), None);
+    }
+
+    #[test]
+    fn version_from_version_info() {
+        let data = pe_with_version([8, 3, 22, 1709]);
+        assert_eq!(arch_from_pe(&data), Ok(V8Arch::X86));
+        assert_eq!(version_from_pe(&data), Ok(V8Version::new(8, 3, 22, 1709)));
+    }
+
+    #[test]
+    fn version_from_pe_without_version_info() {
+        assert!(version_from_pe(&pe(IMAGE_FILE_MACHINE_AMD64, 0x80)).is_err());
+        assert!(version_from_pe(&pe_with_version([8, 3, 22, 1709])[..0x250]).is_err());
+
+        // Ресурс VS_VERSIONINFO без структуры VS_FIXEDFILEINFO
+        let mut without_fixed_info = pe_with_version([8, 3, 22, 1709]);
+        without_fixed_info[0x258 + 2..0x258 + 4].copy_from_slice(&0u16.to_le_bytes());
+        without_fixed_info[0x258..0x258 + 2].copy_from_slice(&0x26u16.to_le_bytes());
+        assert_eq!(version_from_pe(&without_fixed_info), Err("VS_VERSIONINFO has no fixed file info".to_owned()));
+    }
+
+    #[test]
+    fn thin_macho_architectures() {
+        assert!(is_macho(&macho(CPU_TYPE_ARM64)));
+        assert_eq!(arch_from_macho(&macho(CPU_TYPE_X86_64), None), Ok(V8Arch::X64));
+        assert_eq!(arch_from_macho(&macho(CPU_TYPE_ARM64), Some(V8Arch::X64)), Ok(V8Arch::Arm64));
+
+        let mut macho_32 = macho(CPU_TYPE_X86);
+        macho_32[..4].copy_from_slice(&MACHO_MAGIC_32.to_le_bytes());
+        assert_eq!(arch_from_macho(&macho_32, None), Ok(V8Arch::X86));
+
+        assert!(arch_from_macho(&macho(12), None).is_err());
+        assert!(!is_macho(&elf(ELF_CLASS_64, ELF_DATA_LSB, EM_X86_64)));
+    }
+
+    #[test]
+    fn fat_macho_prefers_native_arch() {
+        for is_64 in [false, true] {
+            let universal = fat(&[CPU_TYPE_X86_64, CPU_TYPE_ARM64], is_64);
+            assert!(is_macho(&universal));
+            assert_eq!(arch_from_macho(&universal, Some(V8Arch::Arm64)), Ok(V8Arch::Arm64));
+            assert_eq!(arch_from_macho(&universal, Some(V8Arch::X64)), Ok(V8Arch::X64));
+            assert_eq!(arch_from_macho(&universal, Some(V8Arch::E2k)), Ok(V8Arch::X64));
+            assert_eq!(arch_from_macho(&universal, None), Ok(V8Arch::X64));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_fat_macho() {
+        assert!(arch_from_macho(&fat(&[12], false), Some(V8Arch::Arm64)).is_err());
+        assert!(arch_from_macho(&fat(&[], true), None).is_err());
+
+        let truncated = fat(&[CPU_TYPE_X86_64, CPU_TYPE_ARM64], false);
+        assert!(arch_from_macho(&truncated[..30], None).is_err());
+    }
+}
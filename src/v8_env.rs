@@ -0,0 +1,302 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::io::{Error, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use std::{env, fs, io};
+
+/// Окружение, в котором выполняется поиск: текущая ОС, переменные окружения, домашний каталог
+/// и доступ к файловой системе. Позволяет подменить реальную систему, например, для проверки логики
+/// поиска под Windows на Linux с помощью [MemoryEnvironment]
+pub trait Environment: Send + Sync {
+    /// Имя ОС в формате [std::env::consts::OS]: `windows`, `linux`, `macos`
+    fn os(&self) -> &str;
+
+    /// Архитектура компьютера в формате [std::env::consts::ARCH]: `x86`, `x86_64`, `aarch64`.
+    /// По умолчанию - архитектура, под которую собран текущий процесс
+    fn arch(&self) -> &str {
+        env::consts::ARCH
+    }
+
+    /// Значение переменной окружения
+    fn var_os(&self, name: &str) -> Option<OsString>;
+
+    /// Домашний каталог текущего пользователя
+    fn home_dir(&self) -> Option<PathBuf>;
+
+    /// Читает содержимое файла целиком
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Читает не более `len` первых байт файла. Используется для разбора заголовков исполняемых файлов
+    /// без чтения их целиком. Реализация по умолчанию читает файл целиком и обрезает результат
+    fn read_prefix(&self, path: &Path, len: usize) -> io::Result<Vec<u8>> {
+        let mut buf = self.read(path)?;
+        buf.truncate(len);
+        Ok(buf)
+    }
+
+    /// Возвращает пути ко всем элементам каталога
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Проверяет существование файла или каталога
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Время последнего изменения файла или каталога. Используется для проверки актуальности кэша
+    /// результатов поиска. Реализация по умолчанию возвращает ошибку `Unsupported`, и кэш не используется
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        Err(Error::new(ErrorKind::Unsupported, format!("can't get modification time of {}", path.display())))
+    }
+}
+
+/// Реальное окружение текущего процесса
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemEnvironment;
+
+impl Environment for SystemEnvironment {
+    fn os(&self) -> &str {
+        env::consts::OS
+    }
+
+    fn var_os(&self, name: &str) -> Option<OsString> {
+        env::var_os(name)
+    }
+
+    /// Каталог из переменной `HOME` (`USERPROFILE` в Windows). Если переменная не задана, в Unix-системах
+    /// каталог берется из учетной записи текущего пользователя (`getpwuid_r`, с учетом NSS)
+    fn home_dir(&self) -> Option<PathBuf> {
+        let home_var = match self.os() {
+            "windows" => "USERPROFILE",
+            _ => "HOME",
+        };
+        self.var_os(home_var)
+            .filter(|home| !home.is_empty())
+            .map(PathBuf::from)
+            .or_else(home_dir_from_passwd)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn read_prefix(&self, path: &Path, len: usize) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(len);
+        fs::File::open(path)?.take(len as u64).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        fs::metadata(path)?.modified()
+    }
+}
+
+/// Окружение, полностью хранящееся в памяти. Пути сравниваются без учета вида разделителя (`/` или `\`),
+/// а для ОС `windows` еще и без учета регистра, так что дерево каталогов Windows можно описать
+/// и проверить на любой ОС.
+///```
+/// use std::path::Path;
+/// use v8find4rs::v8_env::{Environment, MemoryEnvironment};
+///
+/// let env = MemoryEnvironment::new("windows")
+///     .with_var("APPDATA", r"C:\Users\user\AppData\Roaming")
+///     .with_dir(r"C:\Program Files\1cv8\8.3.22.1709\bin");
+///
+/// assert!(env.exists(Path::new(r"c:\program files\1cv8")));
+/// assert_eq!(env.read_dir(Path::new(r"C:\Program Files\1cv8")).unwrap().len(), 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemoryEnvironment {
+    os: String,
+    arch: Option<String>,
+    vars: HashMap<String, OsString>,
+    home_dir: Option<PathBuf>,
+    // Ключ - нормализованный путь, значение - путь с исходным регистром
+    files: BTreeMap<String, (String, Vec<u8>)>,
+    dirs: BTreeMap<String, String>,
+}
+
+impl MemoryEnvironment {
+    /// Создание пустого окружения для указанной ОС
+    pub fn new(os: &str) -> Self {
+        MemoryEnvironment {
+            os: os.to_owned(),
+            ..MemoryEnvironment::default()
+        }
+    }
+
+    /// Задает архитектуру компьютера. По умолчанию - архитектура текущего процесса
+    pub fn with_arch(mut self, arch: &str) -> Self {
+        self.arch = Some(arch.to_owned());
+        self
+    }
+
+    /// Задает переменную окружения
+    pub fn with_var<V: Into<OsString>>(mut self, name: &str, value: V) -> Self {
+        let key = self.var_key(name);
+        self.vars.insert(key, value.into());
+        self
+    }
+
+    /// Задает домашний каталог пользователя
+    pub fn with_home<P: Into<PathBuf>>(mut self, home_dir: P) -> Self {
+        self.home_dir = Some(home_dir.into());
+        self
+    }
+
+    /// Добавляет файл с указанным содержимым. Родительские каталоги создаются автоматически
+    pub fn with_file<P: AsRef<Path>, C: Into<Vec<u8>>>(mut self, path: P, contents: C) -> Self {
+        let path = normalize(path.as_ref());
+        if let Some((parent, _)) = path.rsplit_once('/') {
+            self.add_dir(parent.to_owned());
+        }
+        self.files.insert(self.key(&path), (path, contents.into()));
+        self
+    }
+
+    /// Добавляет каталог вместе со всеми родительскими
+    pub fn with_dir<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.add_dir(normalize(path.as_ref()));
+        self
+    }
+
+    fn add_dir(&mut self, mut path: String) {
+        while !path.is_empty() && self.dirs.insert(self.key(&path), path.clone()).is_none() {
+            path = match path.rsplit_once('/') {
+                Some((parent, _)) => parent.to_owned(),
+                None => return,
+            };
+        }
+    }
+
+    fn var_key(&self, name: &str) -> String {
+        if self.os == "windows" {
+            name.to_uppercase()
+        } else {
+            name.to_owned()
+        }
+    }
+
+    fn key(&self, normalized_path: &str) -> String {
+        if self.os == "windows" {
+            normalized_path.to_lowercase()
+        } else {
+            normalized_path.to_owned()
+        }
+    }
+
+    fn path_key(&self, path: &Path) -> String {
+        self.key(&normalize(path))
+    }
+}
+
+impl Environment for MemoryEnvironment {
+    fn os(&self) -> &str {
+        &self.os
+    }
+
+    fn arch(&self) -> &str {
+        self.arch.as_deref().unwrap_or(env::consts::ARCH)
+    }
+
+    fn var_os(&self, name: &str) -> Option<OsString> {
+        self.vars.get(&self.var_key(name)).cloned()
+    }
+
+    fn home_dir(&self) -> Option<PathBuf> {
+        self.home_dir.clone()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files.get(&self.path_key(path))
+            .map(|(_, contents)| contents.clone())
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("{} not found", path.display())))
+    }
+
+    fn read_prefix(&self, path: &Path, len: usize) -> io::Result<Vec<u8>> {
+        self.files.get(&self.path_key(path))
+            .map(|(_, contents)| contents[..len.min(contents.len())].to_vec())
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("{} not found", path.display())))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let key = self.path_key(path);
+        if !self.dirs.contains_key(&key) {
+            return Err(Error::new(ErrorKind::NotFound, format!("{} not found", path.display())));
+        }
+
+        let children = self.dirs.values()
+            .chain(self.files.values().map(|(child, _)| child))
+            .filter_map(|child| child.rsplit_once('/'))
+            .filter(|(parent, _)| self.key(parent) == key)
+            .map(|(_, name)| path.join(name))
+            .collect();
+        Ok(children)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        let key = self.path_key(path);
+        self.dirs.contains_key(&key) || self.files.contains_key(&key)
+    }
+
+    /// Содержимое окружения не меняется после создания, поэтому для всех существующих путей
+    /// возвращается `UNIX_EPOCH`
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        if self.exists(path) {
+            Ok(SystemTime::UNIX_EPOCH)
+        } else {
+            Err(Error::new(ErrorKind::NotFound, format!("{} not found", path.display())))
+        }
+    }
+}
+
+fn normalize(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    path.trim_end_matches('/').to_owned()
+}
+
+// Запись о пользователе запрашивается через getpwuid_r, а не чтением /etc/passwd, чтобы находить
+// и пользователей из NSS (LDAP, SSSD и т.п.)
+#[cfg(unix)]
+fn home_dir_from_passwd() -> Option<PathBuf> {
+    use std::convert::TryFrom;
+    use std::ffi::{CStr, OsStr};
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+    use std::ptr;
+
+    // SAFETY: sysconf и getuid не имеют предусловий
+    let (size_max, uid) = unsafe { (libc::sysconf(libc::_SC_GETPW_R_SIZE_MAX), libc::getuid()) };
+    let mut buf_len = usize::try_from(size_max).ok().filter(|len| *len > 0).unwrap_or(1024);
+
+    loop {
+        let mut buf = vec![0 as libc::c_char; buf_len];
+        let mut passwd = MaybeUninit::<libc::passwd>::uninit();
+        let mut result = ptr::null_mut();
+        // SAFETY: все указатели действительны, размер буфера передается вместе с ним
+        let code = unsafe { libc::getpwuid_r(uid, passwd.as_mut_ptr(), buf.as_mut_ptr(), buf.len(), &mut result) };
+        match code {
+            0 if !result.is_null() => {
+                // SAFETY: при успешном вызове запись заполнена, а pw_dir указывает на строку в buf
+                let home = unsafe { CStr::from_ptr((*result).pw_dir) };
+                return Some(PathBuf::from(OsStr::from_bytes(home.to_bytes())))
+                    .filter(|home| !home.as_os_str().is_empty());
+            }
+            libc::ERANGE if buf_len < 1024 * 1024 => buf_len *= 2,
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn home_dir_from_passwd() -> Option<PathBuf> {
+    None
+}
@@ -0,0 +1,119 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::v8_version::{ParseVersionError, ParseVersionReqError};
+
+/// Ошибки, возникающие при поиске платформ 1С. Перечень может пополняться в новых версиях библиотеки,
+/// поэтому при сопоставлении нужна ветвь `_`
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum V8FindError {
+    /// Строку не удалось разобрать как версию платформы
+    InvalidVersion(ParseVersionError),
+    /// Строку не удалось разобрать как требование к версии платформы
+    InvalidVersionReq(ParseVersionReqError),
+    /// Файл 1CEStart.cfg существует, но прочитать его не удалось
+    StarterConfig {
+        /// Путь к файлу настроек
+        path: PathBuf,
+        /// Исходная ошибка ввода-вывода
+        source: io::Error,
+    },
+    /// Не задана переменная окружения, необходимая для поиска
+    EnvVar {
+        /// Имя переменной окружения
+        name: String,
+    },
+    /// Не удалось разобрать заголовок исполняемого файла платформы
+    PeParse {
+        /// Путь к исполняемому файлу
+        path: PathBuf,
+        /// Описание ошибки разбора
+        message: String,
+    },
+    /// Не удалось разобрать ELF заголовок исполняемого файла платформы
+    ElfParse {
+        /// Путь к исполняемому файлу
+        path: PathBuf,
+        /// Описание ошибки разбора
+        message: String,
+    },
+    /// Не удалось разобрать Mach-O заголовок исполняемого файла платформы
+    MachOParse {
+        /// Путь к исполняемому файлу
+        path: PathBuf,
+        /// Описание ошибки разбора
+        message: String,
+    },
+    /// Не удалось начать отслеживание изменений каталогов установки или файлов настроек
+    Watch {
+        /// Отслеживаемый путь, если ошибка относится к нему
+        path: Option<PathBuf>,
+        /// Описание ошибки
+        message: String,
+    },
+    /// Ошибка ввода-вывода при обращении к каталогу или файлу платформы
+    Io {
+        /// Путь, при обращении к которому возникла ошибка
+        path: PathBuf,
+        /// Исходная ошибка ввода-вывода
+        source: io::Error,
+    },
+}
+
+impl fmt::Display for V8FindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            V8FindError::InvalidVersion(err) => write!(f, "{}", err),
+            V8FindError::InvalidVersionReq(err) => write!(f, "{}", err),
+            V8FindError::StarterConfig { path, source } => {
+                write!(f, "Can't read starter config {}: {}", path.display(), source)
+            }
+            V8FindError::EnvVar { name } => write!(f, "Can't read sys variable {}", name),
+            V8FindError::PeParse { path, message } => {
+                write!(f, "Can't parse PE header of {}: {}", path.display(), message)
+            }
+            V8FindError::ElfParse { path, message } => {
+                write!(f, "Can't parse ELF header of {}: {}", path.display(), message)
+            }
+            V8FindError::MachOParse { path, message } => {
+                write!(f, "Can't parse Mach-O header of {}: {}", path.display(), message)
+            }
+            V8FindError::Watch { path: Some(path), message } => {
+                write!(f, "Can't watch {}: {}", path.display(), message)
+            }
+            V8FindError::Watch { path: None, message } => write!(f, "Can't start watcher: {}", message),
+            V8FindError::Io { path, source } => write!(f, "Can't access {}: {}", path.display(), source),
+        }
+    }
+}
+
+impl Error for V8FindError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            V8FindError::InvalidVersion(err) => Some(err),
+            V8FindError::InvalidVersionReq(err) => Some(err),
+            V8FindError::StarterConfig { source, .. } => Some(source),
+            V8FindError::Io { source, .. } => Some(source),
+            V8FindError::EnvVar { .. }
+            | V8FindError::PeParse { .. }
+            | V8FindError::ElfParse { .. }
+            | V8FindError::MachOParse { .. }
+            | V8FindError::Watch { .. } => None,
+        }
+    }
+}
+
+impl From<ParseVersionError> for V8FindError {
+    fn from(err: ParseVersionError) -> Self {
+        V8FindError::InvalidVersion(err)
+    }
+}
+
+impl From<ParseVersionReqError> for V8FindError {
+    fn from(err: ParseVersionReqError) -> Self {
+        V8FindError::InvalidVersionReq(err)
+    }
+}
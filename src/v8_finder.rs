@@ -1,11 +1,41 @@
 use itertools::Itertools;
 
-use crate::v8_app::V8Arch;
-use crate::v8_finder::v8_platform::V8Platform;
-use log::error;
+use crate::v8_app::{V8Arch, V8Edition};
+use std::cmp::Reverse;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock, RwLock};
+use crate::v8_version::{IntoV8VersionReq, V8VersionReq};
+use crate::v8_env::Environment;
+use crate::v8_error::V8FindError;
+use log::warn;
 
+#[cfg(feature = "cache")]
+mod v8_cache;
+mod v8_common;
 mod v8_dir;
+mod v8_discovery;
+mod v8_finder_builder;
+#[cfg(feature = "serde")]
+mod v8_inventory;
+mod v8_metadata;
 mod v8_platform;
+mod v8_query;
+mod v8_snapshot;
+mod v8_source;
+#[cfg(feature = "watch")]
+mod v8_watcher;
+
+pub use v8_common::{V8CommonDir, V8Starter};
+pub use v8_discovery::{DiscoveryIssue, DiscoverySource};
+pub use v8_finder_builder::V8FinderBuilder;
+use v8_dir::V8Dir;
+use v8_finder_builder::V8FinderConfig;
+pub use v8_platform::V8Platform;
+pub use v8_query::V8Query;
+pub use v8_snapshot::{V8PlatformChange, V8PlatformField, V8Snapshot, V8SnapshotDiff};
+pub use v8_source::{Candidate, DefaultPathsSource, PlatformSource, RootsSource, StarterConfigSource};
+#[cfg(feature = "watch")]
+pub use v8_watcher::{V8WatchEvent, V8Watcher};
 
 /// Определяет приоритетность поиска платформы 1С. Работает аналогично варианту описанному в документации
 /// к платформе
@@ -20,116 +50,535 @@ pub enum SearchPriority {
     /// Ищется 64-х разрядная платформа, но если существует 32-х разрядная платформа более высокой
     /// версии, то будет возвращена именно она.
     X64_32,
+    /// Ищется максимально доступная версия платформы для ARM64 (aarch64, Apple Silicon).
+    Arm64,
+    /// Ищется максимально доступная версия платформы для Эльбруса (e2k).
+    E2k,
+    /// Ищется максимально доступная версия платформы той же архитектуры, что и компьютер, на котором
+    /// выполняется поиск (см. [Environment::arch]).
+    Native,
 }
 
 
-/// Структура для поиска установленных версий платформы 1С.
+/// Структура для поиска установленных версий платформы 1С. Реализует `Send` и `Sync`, так что один
+/// экземпляр можно использовать из нескольких потоков, например, через [V8Finder::shared].
 /// Пример использования:
 ///```no_run
 /// use v8find4rs::v8_finder::{V8Finder, SearchPriority};
 ///
+/// use v8find4rs::v8_version::V8Version;
+///
 /// let finder: V8Finder = V8Finder::new();
 /// let platform = finder.get_platform("8.3.14", SearchPriority::X32);
+/// let exact = finder.get_platform(V8Version::new(8, 3, 14, 1565), SearchPriority::X64);
 /// ```
 pub struct V8Finder {
     platforms: Vec<V8Platform>,
+    issues: Vec<DiscoveryIssue>,
+    common_dirs: Vec<V8CommonDir>,
+    #[cfg_attr(not(feature = "watch"), allow(dead_code))]
+    roots: Vec<PathBuf>,
+    env: Arc<dyn Environment>,
+    config: Arc<V8FinderConfig>,
 }
 
 impl V8Finder {
     /// Создание нового экземпляра. Поиск всех доступных платформ осуществляется сразу при создании объекта.
-    /// В случае возникновения каких-то ошибок при создании объекта, будет осуществлена запись в канал `error`,
-    /// и возвращен объект с пустым вектором доступных платформ
+    /// Ошибка в одном из источников (например, нечитаемый 1CEStart.cfg) не прерывает поиск в остальных:
+    /// такие проблемы записываются в канал `warn` и доступны через [V8Finder::issues]
     pub fn new() -> Self {
-        let platforms = V8Platform::v8_platforms();
-        match platforms {
-            Ok(platforms) => {
-                V8Finder {
-                    platforms,
-                }
-            }
-            Err(err) => {
-                error!("{}", err);
-                V8Finder {platforms: Vec::new()}
-            }
+        V8Finder::builder().build()
+    }
+
+    /// Создание нового экземпляра с возвратом ошибки, если поиск платформ завершился неудачей: не найдено
+    /// ни одной платформы, и при этом хотя бы один из источников не удалось опросить. Возвращается ошибка
+    /// первого такого источника. Отсутствие файлов 1CEStart.cfg и стандартных каталогов установки ошибкой
+    /// не считается
+    pub fn try_new() -> Result<Self, V8FindError> {
+        V8Finder::builder().try_build()
+    }
+
+    /// Возвращает построитель для поиска с нестандартными настройками: дополнительными и исключенными
+    /// каталогами, собственными источниками, без 1CEStart.cfg или стандартных путей установки
+    pub fn builder() -> V8FinderBuilder {
+        V8FinderBuilder::new()
+    }
+
+    fn discover(config: Arc<V8FinderConfig>) -> Self {
+        #[cfg(feature = "cache")]
+        if let Some(finder) = v8_cache::load(&config) {
+            return finder;
         }
+        V8Finder::scan(config)
+    }
+
+    fn scan(config: Arc<V8FinderConfig>) -> Self {
+        let env = &config.env;
+        let mut issues = Vec::new();
+        let (v8_root_dirs, mut platforms) = V8Platform::query_sources(env, &config.sources, &config.excluded_roots,
+                                                                      &mut issues);
+        // Ключ кэша вычисляется до чтения каталогов: если каталог изменится во время поиска,
+        // сохраненный результат будет считаться устаревшим
+        #[cfg(feature = "cache")]
+        let cache_key = v8_cache::CacheKey::new(&config, &v8_root_dirs);
+        let scanned_platforms = V8Platform::v8_platforms(env, &v8_root_dirs, &mut issues);
+        #[cfg(feature = "cache")]
+        v8_cache::store(&config, cache_key, &scanned_platforms);
+
+        platforms.extend(scanned_platforms);
+        V8Finder::assemble(config, v8_root_dirs, platforms, issues)
+    }
+
+    fn assemble(config: Arc<V8FinderConfig>, v8_root_dirs: Vec<V8Dir>, mut platforms: Vec<V8Platform>,
+                issues: Vec<DiscoveryIssue>) -> Self {
+        let env = Arc::clone(&config.env);
+        platforms.extend(config.platforms.iter().cloned());
+        for issue in &issues {
+            warn!("{}", issue);
+        }
+
+        let common_dirs = v8_root_dirs.iter()
+            .filter(|v8_root_dir| env.exists(&v8_root_dir.path))
+            .filter_map(|v8_root_dir| V8CommonDir::find(env.as_ref(), v8_root_dir))
+            .unique_by(|common_dir| common_dir.path.clone())
+            .collect();
+        let roots = v8_root_dirs.into_iter().map(|v8_root_dir| v8_root_dir.path).collect();
+
+        V8Finder { platforms, issues, common_dirs, roots, env, config }
+    }
+
+    /// Общий для всего процесса экземпляр. Поиск выполняется при первом обращении, а с включенной функцией
+    /// `cache` его результат сохраняется в кэше по умолчанию (см. `V8FinderBuilder::default_cache`).
+    /// Для обновления списка платформ новый экземпляр создается через [V8Finder::rescan] под блокировкой
+    /// на чтение, а блокировка на запись берется только для замены, чтобы не останавливать другие потоки
+    /// на время поиска
+    ///```no_run
+    /// use v8find4rs::v8_finder::{SearchPriority, V8Finder};
+    ///
+    /// let shared = V8Finder::shared();
+    /// let platform = shared.read().unwrap().get_platform("8.3", SearchPriority::X64).cloned();
+    /// let rescanned = shared.read().unwrap().rescan();
+    /// *shared.write().unwrap() = rescanned;
+    /// ```
+    pub fn shared() -> Arc<RwLock<V8Finder>> {
+        static SHARED: OnceLock<Arc<RwLock<V8Finder>>> = OnceLock::new();
+        let shared = SHARED.get_or_init(|| {
+            let builder = V8Finder::builder();
+            #[cfg(feature = "cache")]
+            let builder = builder.default_cache();
+            Arc::new(RwLock::new(builder.build()))
+        });
+        Arc::clone(shared)
+    }
+
+    /// Повторяет поиск с теми же настройками, с которыми был создан экземпляр. Сохраненный кэш при этом
+    /// не используется, но обновляется. Для общего экземпляра вместо этого следует использовать
+    /// [V8Finder::rescan], см. [V8Finder::shared]
+    pub fn refresh(&mut self) {
+        *self = self.rescan();
+    }
+
+    /// Повторяет поиск с теми же настройками, что и [V8Finder::refresh], но возвращает новый экземпляр,
+    /// не изменяя текущий
+    pub fn rescan(&self) -> Self {
+        V8Finder::scan(Arc::clone(&self.config))
+    }
+
+    /// Все корневые каталоги, полученные из источников, в том числе еще не существующие
+    #[cfg_attr(not(feature = "watch"), allow(dead_code))]
+    pub(crate) fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Файлы настроек источников (1CEStart.cfg), от которых зависит результат поиска
+    #[cfg_attr(not(feature = "watch"), allow(dead_code))]
+    pub(crate) fn config_files(&self) -> Vec<PathBuf> {
+        self.config.config_files()
+    }
+
+    fn into_result(mut self) -> Result<Self, V8FindError> {
+        if self.platforms.is_empty() && !self.issues.is_empty() {
+            return Err(self.issues.remove(0).error);
+        }
+        Ok(self)
+    }
+
+    /// Все найденные платформы, включая переданные через [V8FinderBuilder::platforms], в порядке обнаружения
+    pub fn platforms(&self) -> &[V8Platform] {
+        &self.platforms
+    }
+
+    /// Снимок найденных платформ для последующего сравнения через [V8Snapshot::diff]. Версии из метаданных
+    /// ([V8Platform::product_version]) ради снимка не читаются, см. [V8Finder::snapshot_with_versions]
+    pub fn snapshot(&self) -> V8Snapshot {
+        V8Snapshot::capture(self, false)
+    }
+
+    /// Аналог [V8Finder::snapshot], в котором для всех платформ прочитаны и версии из метаданных, так что
+    /// [V8Snapshot::diff] сообщает и об их изменении. В Windows для этого исполняемые файлы читаются целиком
+    pub fn snapshot_with_versions(&self) -> V8Snapshot {
+        V8Snapshot::capture(self, true)
+    }
+
+    /// Каталоги common, найденные в корневых каталогах установки
+    pub fn common_dirs(&self) -> &[V8CommonDir] {
+        &self.common_dirs
+    }
+
+    /// Стартер 1С: Предприятия (1cestart) из найденных каталогов common. Если стартеров несколько,
+    /// возвращается стартер максимальной версии, а при равных версиях - из каталога, найденного первым
+    ///```no_run
+    /// use v8find4rs::v8_finder::V8Finder;
+    ///
+    /// let finder = V8Finder::new();
+    /// if let Some(starter) = finder.starter() {
+    ///     println!("{} {:?}", starter.path.display(), starter.version);
+    /// }
+    /// ```
+    pub fn starter(&self) -> Option<V8Starter> {
+        self.common_dirs.iter()
+            .filter_map(|common_dir| common_dir.starter(self.env.as_ref()))
+            .rev()
+            .max_by_key(|starter| starter.version)
+    }
+
+    /// Проблемы, возникшие при опросе источников во время поиска. Платформы из остальных источников
+    /// при этом остаются доступны
+    pub fn issues(&self) -> &[DiscoveryIssue] {
+        &self.issues
     }
 
     /// Осуществляет фильтрацию хранящихся в V8Finder найденных платформ в поисках одной максимально новой
     /// версии
-    /// * version - может быть в трех вариантах: "8.3", "8.3.3" и "8.3.3.1234"
+    /// * version - строка вида "8.3", "8.3.3", "8.3.3.1234" или любое другое требование в формате
+    ///   [V8VersionReq], а также сами [V8Version](crate::v8_version::V8Version) и [V8VersionReq],
+    ///   см. [IntoV8VersionReq]
     /// * search_priority - см. [SearchPriority]
-    pub fn get_platform(&self, version: &str, search_priority: SearchPriority) -> Option<&V8Platform> {
-        let full_version: Vec<_> = version.split(".").collect_vec();
-        return match full_version.len() {
-            // 8.3
-            2 => {
-                let generation: u32 = full_version[1].parse().unwrap();
-                let filtered_platforms: Vec<_> = self.get_platforms_by_filter(
-                    generation, None, None);
-
-                V8Finder::max_platform_by_search_priority(filtered_platforms, search_priority)
-            }
-            // 8.3.3
-            3 => {
-                let generation: u32 = full_version[1].parse().unwrap();
-                let version: u32 = full_version[2].parse().unwrap();
-                let filtered_platforms = self.get_platforms_by_filter(
-                    generation, Some(version), None);
-
-                V8Finder::max_platform_by_search_priority(filtered_platforms, search_priority)
-            }
-            //8.3.3.1234
-            4 => {
-                let generation: u32 = full_version[1].parse().unwrap();
-                let version: u32 = full_version[2].parse().unwrap();
-                let build: u32 = full_version[3].parse().unwrap();
-
-                let filtered_platforms = self.get_platforms_by_filter(
-                    generation, Some(version), Some(build));
-
-                V8Finder::max_platform_by_search_priority(filtered_platforms, search_priority)
-            }
-            _ => None
-        };
-    }
-
-    fn get_platforms_by_filter(&self, generation: u32, version: Option<u32>, build: Option<u32>) -> Vec<&V8Platform> {
-        let mut filtered_platforms = self.platforms.iter().rev()
-            .filter(|platform| platform.generation == generation).collect_vec();
-
-        if version.is_some() {
-            filtered_platforms = filtered_platforms.iter()
-                .copied()
-                .filter(|platform| platform.version == version.unwrap()).collect_vec();
-        }
+    ///
+    /// Если строку не удалось разобрать как требование к версии, возвращается `None`
+    pub fn get_platform<V: IntoV8VersionReq>(&self, version: V, search_priority: SearchPriority) -> Option<&V8Platform> {
+        self.try_get_platform(version, search_priority).ok().flatten()
+    }
 
-        if build.is_some() {
-            filtered_platforms = filtered_platforms.iter()
-                .copied()
-                .filter(|platform| platform.build == build.unwrap())
-                .collect_vec();
-        }
+    /// Аналог [V8Finder::get_platform], возвращающий ошибку [V8FindError::InvalidVersionReq], если строку
+    /// не удалось разобрать как требование к версии
+    pub fn try_get_platform<V: IntoV8VersionReq>(&self, version: V,
+                                                 search_priority: SearchPriority) -> Result<Option<&V8Platform>, V8FindError> {
+        let version_req = version.into_version_req()?;
+        Ok(self.find_platform(&version_req, search_priority))
+    }
 
-        filtered_platforms
+    /// Возвращает максимально новую платформу, удовлетворяющую требованию к версии, с учетом
+    /// приоритета разрядности. Учебная версия ([V8Edition::Training]) не выбирается, для нее
+    /// следует использовать [V8Finder::find_platform_by_edition]. Каталоги версий, в которых не найдено
+    /// ни одного компонента ([V8Edition::Unknown]), также не выбираются
+    ///```no_run
+    /// use v8find4rs::v8_finder::{V8Finder, SearchPriority};
+    /// use v8find4rs::v8_version::V8VersionReq;
+    ///
+    /// let finder = V8Finder::new();
+    /// let version_req: V8VersionReq = ">=8.3.18, <8.3.23".parse().unwrap();
+    /// let platform = finder.find_platform(&version_req, SearchPriority::X64_32);
+    /// ```
+    pub fn find_platform(&self, version_req: &V8VersionReq, search_priority: SearchPriority) -> Option<&V8Platform> {
+        self.find_platform_by_edition(version_req, search_priority,
+                                      &[V8Edition::Full, V8Edition::ThinClientOnly, V8Edition::ServerOnly])
     }
 
-    fn max_platform_by_search_priority(filtered_platforms: Vec<&V8Platform>,
-                                       search_priority: SearchPriority) -> Option<&V8Platform> {
-        let max_x32 = filtered_platforms.iter()
-            .copied()
-            .filter(|platform| platform.arch == V8Arch::X86)
-            .max();
+    /// Аналог [V8Finder::find_platform], выбирающий только платформы указанных вариантов поставки
+    ///```no_run
+    /// use v8find4rs::v8_app::V8Edition;
+    /// use v8find4rs::v8_finder::{V8Finder, SearchPriority};
+    /// use v8find4rs::v8_version::V8VersionReq;
+    ///
+    /// let finder = V8Finder::new();
+    /// let server = finder.find_platform_by_edition(&V8VersionReq::any(), SearchPriority::X64,
+    ///                                              &[V8Edition::Full, V8Edition::ServerOnly]);
+    /// ```
+    pub fn find_platform_by_edition(&self, version_req: &V8VersionReq, search_priority: SearchPriority,
+                                    editions: &[V8Edition]) -> Option<&V8Platform> {
+        let query = V8Query::new().version(version_req.clone()).editions(editions);
+        let filtered_platforms = self.get_platforms_by_filter(&query);
+        self.max_platform_by_search_priority(filtered_platforms, search_priority)
+    }
 
-        let max_x64 = filtered_platforms.iter()
+    /// Возвращает все платформы, удовлетворяющие запросу, от новых версий к старым. Платформы одной
+    /// версии возвращаются в порядке обнаружения. Для обратного порядка достаточно вызвать `rev()`
+    pub fn find_platforms<'a>(&'a self, query: &V8Query) -> impl DoubleEndedIterator<Item=&'a V8Platform> + ExactSizeIterator {
+        let mut platforms: Vec<_> = self.platforms.iter()
+            .filter(|platform| query.matches(platform))
+            .collect();
+        platforms.sort_by_key(|platform| Reverse(platform.version()));
+        platforms.into_iter()
+    }
+
+    fn get_platforms_by_filter(&self, query: &V8Query) -> Vec<&V8Platform> {
+        self.platforms.iter().rev()
+            .filter(|platform| query.matches(platform))
+            .collect_vec()
+    }
+
+    fn max_platform_by_search_priority<'a>(&self, filtered_platforms: Vec<&'a V8Platform>,
+                                           search_priority: SearchPriority) -> Option<&'a V8Platform> {
+        let max_by_arch = |arch: V8Arch| filtered_platforms.iter()
             .copied()
-            .filter(|platform| platform.arch == V8Arch::X64)
-            .max();
+            .filter(|platform| platform.arch() == arch)
+            .max_by_key(|platform| platform.version());
+        // При равных версиях 64-х разрядная платформа предпочтительнее 32-х разрядной
+        let newest = |first: Option<&'a V8Platform>, second: Option<&'a V8Platform>| first.into_iter()
+            .chain(second)
+            .max_by_key(|platform| (platform.version(), platform.arch()));
+
+        let max_x32 = max_by_arch(V8Arch::X86);
+        let max_x64 = max_by_arch(V8Arch::X64);
 
         match search_priority {
             SearchPriority::X32 => max_x32,
             SearchPriority::X64 => max_x64,
-            SearchPriority::X32_64 => max_x64.max(max_x32),
-            SearchPriority::X64_32 => max_x32.max(max_x64),
+            SearchPriority::X32_64 => newest(max_x64, max_x32),
+            SearchPriority::X64_32 => newest(max_x32, max_x64),
+            SearchPriority::Arm64 => max_by_arch(V8Arch::Arm64),
+            SearchPriority::E2k => max_by_arch(V8Arch::E2k),
+            SearchPriority::Native => V8Arch::from_rust_arch(self.env.arch()).and_then(max_by_arch),
         }
     }
-}
\ No newline at end of file
+}
+impl Default for V8Finder {
+    fn default() -> Self {
+        V8Finder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::v8_app::v8_binary::tests::{elf, macho, pe};
+    use crate::v8_env::MemoryEnvironment;
+    use crate::v8_version::V8Version;
+
+    const I386: u16 = 0x14c;
+    const AMD64: u16 = 0x8664;
+
+    fn utf16_with_bom(text: &str) -> Vec<u8> {
+        let mut data = vec![0xff, 0xfe];
+        data.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        data
+    }
+
+    fn windows() -> MemoryEnvironment {
+        MemoryEnvironment::new("windows")
+            .with_var("ProgramFiles", r"C:\Program Files")
+            .with_var("ProgramFiles(x86)", r"C:\Program Files (x86)")
+            .with_var("LOCALAPPDATA", r"C:\Users\user\AppData\Local")
+            .with_var("ALLUSERSPROFILE", r"C:\ProgramData")
+            .with_var("APPDATA", r"C:\Users\user\AppData\Roaming")
+    }
+
+    fn versions(finder: &V8Finder) -> Vec<String> {
+        finder.platforms().iter().map(|platform| platform.version().to_string()).collect()
+    }
+
+    #[test]
+    fn windows_default_paths() {
+        let env = windows()
+            .with_file(r"C:\Program Files\1cv8\8.3.22.1709\bin\1cv8.exe", pe(AMD64, 0x80))
+            .with_file(r"C:\Program Files (x86)\1cv8\8.3.23.1865\bin\1cv8.exe", pe(I386, 0x80))
+            .with_file(r"C:\Users\user\AppData\Local\Programs\1cv8_x64\8.3.24.1342\bin\1cv8c.exe", pe(AMD64, 0x80))
+            .with_dir(r"C:\Program Files\1cv8\common");
+        let finder = V8Finder::builder().environment(env).build();
+
+        assert!(finder.issues().is_empty(), "{:?}", finder.issues());
+        assert_eq!(versions(&finder), ["8.3.23.1865", "8.3.22.1709", "8.3.24.1342"]);
+        assert!(finder.platforms().iter().all(|platform| platform.source() == &DiscoverySource::DefaultPaths));
+        assert_eq!(finder.platforms()[0].root(), Path::new(r"C:\Program Files (x86)").join("1cv8"));
+
+        let version_req: V8VersionReq = "8.3".parse().unwrap();
+        let x32 = finder.find_platform(&version_req, SearchPriority::X32).unwrap();
+        assert_eq!((x32.version().to_string(), x32.arch()), ("8.3.23.1865".to_owned(), V8Arch::X86));
+        let x64 = finder.find_platform(&version_req, SearchPriority::X64).unwrap();
+        assert_eq!((x64.version().to_string(), x64.arch()), ("8.3.24.1342".to_owned(), V8Arch::X64));
+        assert_eq!(finder.common_dirs().len(), 1);
+    }
+
+    #[test]
+    fn starter_of_max_version() {
+        use crate::v8_app::v8_binary::tests::pe_with_version;
+
+        let env = windows()
+            .with_file(r"C:\Program Files\1cv8\8.3.22.1709\bin\1cv8.exe", pe(AMD64, 0x80))
+            .with_file(r"C:\Program Files\1cv8\common\1cestart.exe", pe_with_version([8, 3, 22, 1709]))
+            .with_file(r"C:\Program Files (x86)\1cv8\common\1cestart.exe", pe_with_version([8, 3, 23, 1865]));
+        let finder = V8Finder::builder().environment(env).build();
+        assert_eq!(finder.common_dirs().len(), 2);
+
+        let common_dir = Path::new(r"C:\Program Files (x86)").join("1cv8").join("common");
+        let starter = finder.starter().unwrap();
+        assert_eq!(starter.path, common_dir.join("1cestart.exe"));
+        assert_eq!(starter.version, Some(V8Version::new(8, 3, 23, 1865)));
+        assert_eq!(starter.common_dir, common_dir);
+    }
+
+    #[test]
+    fn linux_starter_next_to_arch_dir() {
+        use crate::v8_app::V8AppType;
+
+        let env = MemoryEnvironment::new("linux")
+            .with_home("/home/user")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/1cv8", elf(2, 1, 62))
+            .with_file("/opt/1cv8/common/1cestart", "");
+        let finder = V8Finder::builder().environment(env).build();
+
+        let starter = finder.starter().unwrap();
+        assert_eq!(starter.path, Path::new("/opt/1cv8/common/1cestart"));
+        assert_eq!(starter.version, None);
+        let platform = finder.get_platform("8.3", SearchPriority::X64).unwrap();
+        assert_eq!(platform.get_app_by_type(V8AppType::Starter), Some(starter.path));
+    }
+
+    #[test]
+    fn without_starter() {
+        let env = MemoryEnvironment::new("linux")
+            .with_home("/home/user")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/1cv8", elf(2, 1, 62))
+            .with_dir("/opt/1cv8/common");
+        let finder = V8Finder::builder().environment(env).build();
+
+        assert_eq!(finder.common_dirs().len(), 1);
+        assert_eq!(finder.starter(), None);
+    }
+
+    #[test]
+    fn empty_version_dirs_are_not_selected() {
+        // Архитектура пустого каталога определяется по пути, но компонентов в нем нет
+        let env = MemoryEnvironment::new("linux")
+            .with_home("/home/user")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/1cv8", elf(2, 1, 62))
+            .with_dir("/opt/1cv8/x86_64/8.3.23.1865");
+        let finder = V8Finder::builder().environment(env).build();
+
+        assert_eq!(versions(&finder), ["8.3.22.1709", "8.3.23.1865"]);
+        assert_eq!(finder.platforms()[1].arch(), V8Arch::X64);
+        assert_eq!(finder.platforms()[1].edition(), V8Edition::Unknown);
+        let platform = finder.get_platform("8.3", SearchPriority::X64).unwrap();
+        assert_eq!(platform.version().to_string(), "8.3.22.1709");
+        let unknown = finder.find_platform_by_edition(&V8VersionReq::any(), SearchPriority::X64, &[V8Edition::Unknown]);
+        assert_eq!(unknown.unwrap().version().to_string(), "8.3.23.1865");
+    }
+
+    #[test]
+    fn get_platform_accepts_typed_requirements() {
+        let env = MemoryEnvironment::new("linux")
+            .with_file("/opt/1c/8.3.22.1709/1cv8", elf(2, 1, 62))
+            .with_file("/opt/1c/8.3.23.1865/1cv8", elf(2, 1, 62));
+        let finder = V8Finder::builder().no_defaults().root("/opt/1c").environment(env).build();
+        let get = |platform: Option<&V8Platform>| platform.map(|platform| platform.version().to_string());
+
+        let version = V8Version::new(8, 3, 22, 1709);
+        assert_eq!(get(finder.get_platform(version, SearchPriority::X64)).as_deref(), Some("8.3.22.1709"));
+        let version_req: V8VersionReq = "<8.3.23".parse().unwrap();
+        assert_eq!(get(finder.get_platform(&version_req, SearchPriority::X64)).as_deref(), Some("8.3.22.1709"));
+        assert_eq!(get(finder.get_platform(String::from("8.3"), SearchPriority::X64)).as_deref(), Some("8.3.23.1865"));
+        assert!(matches!(finder.try_get_platform("8.3.a", SearchPriority::X64), Err(V8FindError::InvalidVersionReq(_))));
+    }
+
+    #[test]
+    fn windows_without_program_files_x86() {
+        let env = MemoryEnvironment::new("windows")
+            .with_var("ProgramFiles", r"C:\Program Files")
+            .with_var("ALLUSERSPROFILE", r"C:\ProgramData")
+            .with_var("APPDATA", r"C:\Users\user\AppData\Roaming")
+            .with_file(r"C:\Program Files\1cv8\8.3.22.1709\bin\1cv8.exe", pe(AMD64, 0x80));
+        let finder = V8Finder::builder().environment(env).build();
+
+        assert!(finder.issues().is_empty(), "{:?}", finder.issues());
+        assert_eq!(versions(&finder), ["8.3.22.1709"]);
+    }
+
+    #[test]
+    fn windows_starter_configs() {
+        let env = windows()
+            .with_file(r"C:\ProgramData\1C\1CEStart\1CEStart.cfg",
+                       utf16_with_bom("DefaultVersionX86=8.3\r\nInstalledLocation=D:\\1C\\platforms\r\n"))
+            .with_file(r"C:\Users\user\AppData\Roaming\1C\1CEStart\1CEStart.cfg",
+                       "InstalledLocation=E:\\portable\\1cv8\r\nInstalledLocation=D:\\1C\\platforms\r\n")
+            .with_file(r"D:\1C\platforms\8.3.21.1895\bin\1cv8.exe", pe(AMD64, 0x80))
+            .with_file(r"E:\portable\1cv8\8.3.20.2290\bin\1cv8c.exe", pe(I386, 0x80));
+        let finder = V8Finder::builder().environment(env).build();
+
+        assert!(finder.issues().is_empty(), "{:?}", finder.issues());
+        assert_eq!(versions(&finder), ["8.3.21.1895", "8.3.20.2290"]);
+        // Каталог из обоих файлов учитывается один раз, с первым источником
+        assert_eq!(finder.platforms()[0].source(), &DiscoverySource::AllUsersStarter);
+        assert_eq!(finder.platforms()[1].source(), &DiscoverySource::CurrentUserStarter);
+        assert_eq!(finder.platforms()[1].arch(), V8Arch::X86);
+    }
+
+    #[test]
+    fn windows_missing_appdata_is_an_issue() {
+        let env = MemoryEnvironment::new("windows")
+            .with_var("ProgramFiles", r"C:\Program Files")
+            .with_var("ALLUSERSPROFILE", r"C:\ProgramData")
+            .with_file(r"C:\Program Files\1cv8\8.3.22.1709\bin\1cv8.exe", pe(AMD64, 0x80));
+        let finder = V8Finder::builder().environment(env.clone()).build();
+
+        assert_eq!(versions(&finder), ["8.3.22.1709"]);
+        assert_eq!(finder.issues().len(), 1);
+        let issue = &finder.issues()[0];
+        assert_eq!(issue.source, DiscoverySource::CurrentUserStarter);
+        assert!(matches!(&issue.error, V8FindError::EnvVar { name } if name == "APPDATA"));
+
+        // Без найденных платформ проблема становится ошибкой поиска
+        let result = V8Finder::builder().environment(env).default_paths(false).try_build();
+        assert!(matches!(result, Err(V8FindError::EnvVar { name }) if name == "APPDATA"));
+    }
+
+    #[test]
+    fn linux_default_paths_and_starter_configs() {
+        let env = MemoryEnvironment::new("linux")
+            .with_home("/home/user")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/1cv8", elf(2, 1, 62))
+            .with_file("/opt/1cv8/i386/8.3.18.1741/1cv8", elf(1, 1, 3))
+            .with_file("/etc/1C/1cestart/1cestart.cfg", "InstalledLocation=/srv/1c\n")
+            .with_file("/home/user/.1C/1cestart/1cestart.cfg", "\u{feff}InstalledLocation=/home/user/1c\n")
+            .with_file("/srv/1c/8.3.23.1865/1cv8s", elf(2, 1, 183))
+            .with_file("/home/user/1c/8.3.24.1342/1cv8c", elf(2, 1, 62));
+        let finder = V8Finder::builder().environment(env).build();
+
+        assert!(finder.issues().is_empty(), "{:?}", finder.issues());
+        assert_eq!(versions(&finder), ["8.3.23.1865", "8.3.24.1342", "8.3.18.1741", "8.3.22.1709"]);
+        let sources: Vec<_> = finder.platforms().iter().map(V8Platform::source).collect();
+        assert_eq!(sources, [&DiscoverySource::AllUsersStarter, &DiscoverySource::CurrentUserStarter,
+                             &DiscoverySource::DefaultPaths, &DiscoverySource::DefaultPaths]);
+        assert_eq!(finder.platforms()[0].arch(), V8Arch::Arm64);
+        assert_eq!(finder.get_platform("8.3", SearchPriority::X32).unwrap().version().to_string(), "8.3.18.1741");
+    }
+
+    #[test]
+    fn linux_without_home_is_an_issue() {
+        let env = MemoryEnvironment::new("linux")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/1cv8", elf(2, 1, 62));
+        let finder = V8Finder::builder().environment(env).build();
+
+        assert_eq!(versions(&finder), ["8.3.22.1709"]);
+        assert_eq!(finder.issues().len(), 1);
+        assert!(matches!(&finder.issues()[0].error, V8FindError::EnvVar { name } if name == "HOME"));
+    }
+
+    #[test]
+    fn macos_default_paths() {
+        let env = MemoryEnvironment::new("macos")
+            .with_arch("aarch64")
+            .with_home("/Users/user")
+            .with_file("/opt/1cv8/8.3.22.1709/1cv8.app/Contents/MacOS/1cv8", macho(0x0100_000c))
+            .with_file("/opt/1cv8/8.3.21.1895/1cv8.app/Contents/MacOS/1cv8", macho(0x0100_0007));
+        let finder = V8Finder::builder().environment(env).build();
+
+        assert!(finder.issues().is_empty(), "{:?}", finder.issues());
+        assert_eq!(finder.platforms().len(), 2);
+        let native = finder.get_platform("8.3", SearchPriority::Native).unwrap();
+        assert_eq!((native.version().to_string(), native.arch()), ("8.3.22.1709".to_owned(), V8Arch::Arm64));
+        assert_eq!(finder.get_platform("8.3", SearchPriority::X64).unwrap().version().to_string(), "8.3.21.1895");
+    }
+}
@@ -0,0 +1,323 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::v8_env::Environment;
+use crate::v8_error::V8FindError;
+use crate::v8_finder::v8_dir::V8Dir;
+use crate::v8_finder::v8_finder_builder::V8FinderConfig;
+use crate::v8_finder::v8_platform::{CachedPlatform, V8Platform};
+use crate::v8_finder::V8Finder;
+
+// Увеличивается при несовместимом изменении формата, старый кэш при этом просто не используется
+const CACHE_FORMAT: u32 = 1;
+const CACHE_FILE_NAME: &str = "platforms.json";
+
+/// Расположение файла кэша, заданное в [V8FinderBuilder](crate::v8_finder::V8FinderBuilder)
+#[derive(Debug, Clone)]
+pub(crate) enum CacheLocation {
+    Default,
+    Path(PathBuf),
+}
+
+impl CacheLocation {
+    pub(crate) fn resolve(self, env: &dyn Environment) -> Option<PathBuf> {
+        match self {
+            CacheLocation::Default => default_cache_dir(env).map(|dir| dir.join("v8find4rs").join(CACHE_FILE_NAME)),
+            CacheLocation::Path(path) => Some(path),
+        }
+    }
+}
+
+fn default_cache_dir(env: &dyn Environment) -> Option<PathBuf> {
+    match env.os() {
+        "windows" => env.var_os("LOCALAPPDATA").map(PathBuf::from),
+        "macos" => env.home_dir().map(|home| home.join("Library").join("Caches")),
+        _ => env.var_os("XDG_CACHE_HOME")
+            .filter(|cache_dir| !cache_dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env.home_dir().map(|home| home.join(".cache"))),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct PathStamp {
+    path: PathBuf,
+    // None - путь не существует
+    modified: Option<SystemTime>,
+}
+
+/// Время изменения файлов 1CEStart.cfg и корневых каталогов на момент поиска. Кэш действителен, пока
+/// ключ совпадает с текущим
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct CacheKey {
+    config_files: Vec<PathStamp>,
+    roots: Vec<PathStamp>,
+}
+
+impl CacheKey {
+    /// Вычисляет ключ, если для поиска задан кэш. `None`, если время изменения одного из путей
+    /// получить не удалось
+    pub(crate) fn new(config: &V8FinderConfig, v8_root_dirs: &[V8Dir]) -> Option<CacheKey> {
+        config.cache.as_ref()?;
+        let env = config.env.as_ref();
+        let config_files = stamps(env, config.config_files())?;
+        let roots = stamps(env, v8_root_dirs.iter().map(|v8_root_dir| v8_root_dir.path.clone()))?;
+        Some(CacheKey { config_files, roots })
+    }
+}
+
+fn stamps<I: IntoIterator<Item=PathBuf>>(env: &dyn Environment, paths: I) -> Option<Vec<PathStamp>> {
+    paths.into_iter()
+        .map(|path| match env.modified(&path) {
+            Ok(modified) => Some(PathStamp { path, modified: Some(modified) }),
+            Err(err) if err.kind() == ErrorKind::NotFound => Some(PathStamp { path, modified: None }),
+            Err(_) => None,
+        })
+        .collect()
+}
+
+// Сохраняются только платформы из корневых каталогов: источники опрашиваются при каждом запуске,
+// а платформы из V8FinderBuilder::platforms хранятся в настройках
+#[derive(Serialize)]
+struct CacheRef<'a> {
+    format: u32,
+    os: &'a str,
+    arch: &'a str,
+    key: CacheKey,
+    platforms: Vec<CachedPlatform>,
+}
+
+#[derive(Deserialize)]
+struct Cache {
+    format: u32,
+    os: String,
+    arch: String,
+    key: CacheKey,
+    platforms: Vec<CachedPlatform>,
+}
+
+/// Восстанавливает [V8Finder] из кэша, если он задан и действителен. Источники при этом опрашиваются,
+/// но корневые каталоги не читаются. Проблемы, возникшие при чтении корневых каталогов во время
+/// исходного поиска, в кэше не сохраняются
+pub(crate) fn load(config: &Arc<V8FinderConfig>) -> Option<V8Finder> {
+    let cache_path = config.cache.as_ref()?;
+    let data = match fs::read(cache_path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == ErrorKind::NotFound => return None,
+        Err(err) => {
+            warn!("{}", V8FindError::Io { path: cache_path.clone(), source: err });
+            return None;
+        }
+    };
+    let cache: Cache = match serde_json::from_slice(&data) {
+        Ok(cache) => cache,
+        Err(err) => {
+            warn!("{}", V8FindError::Io { path: cache_path.clone(), source: err.into() });
+            return None;
+        }
+    };
+
+    let env = &config.env;
+    if cache.format != CACHE_FORMAT || cache.os != env.os() || cache.arch != env.arch() {
+        return None;
+    }
+
+    let mut issues = Vec::new();
+    let (v8_root_dirs, mut platforms) = V8Platform::query_sources(env, &config.sources, &config.excluded_roots,
+                                                                  &mut issues);
+    if CacheKey::new(config, &v8_root_dirs).as_ref() != Some(&cache.key) {
+        return None;
+    }
+
+    let packages = Arc::default();
+    platforms.extend(cache.platforms.into_iter().map(|platform| platform.into_platform(env, &packages)));
+    Some(V8Finder::assemble(Arc::clone(config), v8_root_dirs, platforms, issues))
+}
+
+/// Сохраняет платформы, найденные в корневых каталогах. Перед сохранением определяются архитектура,
+/// состав компонентов и вариант поставки: для них читаются только заголовки исполняемых файлов и содержимое
+/// каталогов версий, а без них загрузка из кэша не избавляла бы от чтения диска. Версия из метаданных
+/// не определяется и не сохраняется. Файл заменяется целиком, так что параллельно работающие процессы
+/// не прочитают его частично записанным. Ошибка записи не мешает поиску и записывается в канал `warn`
+pub(crate) fn store(config: &V8FinderConfig, key: Option<CacheKey>, platforms: &[V8Platform]) {
+    let (cache_path, key) = match (&config.cache, key) {
+        (Some(cache_path), Some(key)) => (cache_path, key),
+        _ => return,
+    };
+    for platform in platforms {
+        platform.arch();
+        platform.edition();
+    }
+
+    let cache = CacheRef {
+        format: CACHE_FORMAT,
+        os: config.env.os(),
+        arch: config.env.arch(),
+        key,
+        platforms: platforms.iter().map(CachedPlatform::new).collect(),
+    };
+    let result = serde_json::to_vec(&cache)
+        .map_err(io::Error::from)
+        .and_then(|data| write_atomically(cache_path, &data));
+
+    if let Err(err) = result {
+        warn!("{}", V8FindError::Io { path: cache_path.clone(), source: err });
+    }
+}
+
+// Временный файл создается в том же каталоге, чтобы rename не пересекал границу файловых систем.
+// Имя уникально для процесса и вызова, так что одновременная запись из разных потоков не конфликтует
+fn write_atomically(path: &Path, data: &[u8]) -> io::Result<()> {
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let cache_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    fs::create_dir_all(cache_dir)?;
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let temp_path = cache_dir.join(format!(".{}.{}.{}.tmp", file_name, process::id(),
+                                           TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)));
+
+    let result = fs::write(&temp_path, data).and_then(|()| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::*;
+    use crate::v8_app::v8_binary::tests::elf;
+    use crate::v8_env::MemoryEnvironment;
+
+    fn build(env: MemoryEnvironment, cache_path: &Path) -> V8Finder {
+        V8Finder::builder().no_defaults().root("/opt/1c").environment(env).cache(cache_path).build()
+    }
+
+    // Считает обращения к содержимому файлов и каталогов версий
+    struct CountingEnvironment {
+        env: MemoryEnvironment,
+        reads: Arc<AtomicUsize>,
+    }
+
+    impl CountingEnvironment {
+        fn count(&self, path: &Path) {
+            if path != Path::new("/opt/1c") {
+                self.reads.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    impl Environment for CountingEnvironment {
+        fn os(&self) -> &str {
+            self.env.os()
+        }
+
+        fn var_os(&self, name: &str) -> Option<OsString> {
+            self.env.var_os(name)
+        }
+
+        fn home_dir(&self) -> Option<PathBuf> {
+            self.env.home_dir()
+        }
+
+        fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+            self.count(path);
+            self.env.read(path)
+        }
+
+        fn read_prefix(&self, path: &Path, len: usize) -> io::Result<Vec<u8>> {
+            self.count(path);
+            self.env.read_prefix(path, len)
+        }
+
+        fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+            self.count(path);
+            self.env.read_dir(path)
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            self.env.exists(path)
+        }
+
+        fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+            self.env.modified(path)
+        }
+    }
+
+    #[test]
+    fn stores_determined_fields_and_loads_them_back() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache_path = cache_dir.path().join("v8find4rs").join(CACHE_FILE_NAME);
+        let env = MemoryEnvironment::new("linux")
+            .with_file("/opt/1c/8.3.22.1709/1cv8", elf(2, 1, 62))
+            .with_file("/opt/1c/8.3.23.1865/1cv8c", elf(2, 1, 183));
+
+        let scanned = build(env, &cache_path);
+        assert_eq!(scanned.platforms().len(), 2);
+        let json: serde_json::Value = serde_json::from_slice(&fs::read(&cache_path).unwrap()).unwrap();
+        for platform in json["platforms"].as_array().unwrap() {
+            for field in ["arch", "edition", "components"] {
+                assert!(platform.get(field).is_some(), "no {} in {}", field, platform);
+            }
+            assert!(platform.get("product_version").is_none(), "product_version in {}", platform);
+        }
+        let files: Vec<_> = fs::read_dir(cache_path.parent().unwrap()).unwrap().collect();
+        assert_eq!(files.len(), 1, "temporary file left behind");
+
+        // Каталоги версий в новом окружении пусты, но время изменения корня то же, поэтому используется
+        // кэш, и ни заголовки исполняемых файлов, ни каталоги версий не читаются
+        let reads = Arc::new(AtomicUsize::new(0));
+        let env = CountingEnvironment {
+            env: MemoryEnvironment::new("linux")
+                .with_dir("/opt/1c/8.3.22.1709")
+                .with_dir("/opt/1c/8.3.23.1865"),
+            reads: Arc::clone(&reads),
+        };
+        let cached = V8Finder::builder().no_defaults().root("/opt/1c").environment(env).cache(&cache_path).build();
+        let details = |finder: &V8Finder| finder.platforms().iter()
+            .map(|platform| (platform.path().to_path_buf(), platform.arch(), platform.edition(),
+                             platform.components().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(details(&cached), details(&scanned));
+        assert_eq!(cached.platforms()[1].arch(), crate::v8_app::V8Arch::Arm64);
+        assert_eq!(reads.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn cached_platform_keeps_determined_fields() {
+        let env = MemoryEnvironment::new("linux").with_file("/opt/1c/8.3.22.1709/1cv8", elf(2, 1, 62));
+        let finder = V8Finder::builder().no_defaults().root("/opt/1c").environment(env.clone()).build();
+        let platform = &finder.platforms()[0];
+        platform.arch();
+        platform.components();
+
+        let json = serde_json::to_string(&CachedPlatform::new(platform)).unwrap();
+        assert!(json.contains("\"arch\"") && json.contains("\"components\""), "{}", json);
+        assert!(!json.contains("\"edition\"") && !json.contains("\"product_version\""), "{}", json);
+
+        let env: Arc<dyn Environment> = Arc::new(env);
+        let restored = serde_json::from_str::<CachedPlatform>(&json).unwrap().into_platform(&env, &Arc::default());
+        assert_eq!(restored.arch_source(), platform.arch_source());
+        assert_eq!(restored.components(), platform.components());
+    }
+
+    #[test]
+    fn atomic_write_replaces_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CACHE_FILE_NAME);
+        fs::write(&path, "old").unwrap();
+        write_atomically(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,112 @@
+use std::path::{Path, PathBuf};
+
+use crate::v8_env::Environment;
+use crate::v8_finder::v8_dir::V8Dir;
+use crate::v8_finder::v8_discovery::DiscoverySource;
+use crate::v8_finder::v8_metadata::{self, PackageIndex};
+use crate::v8_version::V8Version;
+
+/// Каталог common с общими для всех версий файлами, в том числе стартером 1cestart. В Windows
+/// находится внутри корневого каталога установки (`C:\Program Files\1cv8\common`), в Linux - рядом
+/// с каталогом архитектуры (`/opt/1cv8/common` для `/opt/1cv8/x86_64`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct V8CommonDir {
+    /// Путь к каталогу common
+    pub path: PathBuf,
+    /// Корневой каталог установки, для которого найден каталог common
+    pub root: PathBuf,
+    /// Источник, из которого был получен корневой каталог
+    pub source: DiscoverySource,
+}
+
+/// Стартер 1С: Предприятия (1cestart), через который пользователи запускают информационные базы
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct V8Starter {
+    /// Путь к исполняемому файлу стартера
+    pub path: PathBuf,
+    /// Версия из метаданных файла: ресурса VS_VERSIONINFO в Windows, базы dpkg в Linux,
+    /// Info.plist в macOS. `None`, если ее не удалось прочитать
+    pub version: Option<V8Version>,
+    /// Каталог common, в котором найден стартер
+    pub common_dir: PathBuf,
+}
+
+impl V8CommonDir {
+    pub(crate) fn find(env: &dyn Environment, v8_root_dir: &V8Dir) -> Option<V8CommonDir> {
+        let mut candidates = vec![v8_root_dir.path.join("common")];
+        if env.os() != "windows" {
+            candidates.extend(v8_root_dir.path.parent().map(|parent| parent.join("common")));
+        }
+
+        candidates.into_iter()
+            .find(|path| env.exists(path))
+            .map(|path| V8CommonDir { path, root: v8_root_dir.path.clone(), source: v8_root_dir.source.clone() })
+    }
+
+    /// Путь к стартеру в каталоге common, если он существует
+    pub fn starter_path(&self, env: &dyn Environment) -> Option<PathBuf> {
+        starter_candidates(env.os(), &self.path).into_iter().find(|path| env.exists(path))
+    }
+
+    pub(crate) fn starter(&self, env: &dyn Environment) -> Option<V8Starter> {
+        let path = self.starter_path(env)?;
+        let version = v8_metadata::read_file_version(env, &PackageIndex::default(), &path);
+        Some(V8Starter { path, version, common_dir: self.path.clone() })
+    }
+}
+
+fn starter_candidates(os: &str, common_dir: &Path) -> Vec<PathBuf> {
+    match os {
+        "windows" => vec![common_dir.join("1cestart.exe")],
+        "macos" => vec![
+            common_dir.join("1cestart"),
+            common_dir.join("1cestart.app").join("Contents").join("MacOS").join("1cestart"),
+        ],
+        _ => vec![common_dir.join("1cestart")],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::v8_env::MemoryEnvironment;
+
+    fn find(env: &MemoryEnvironment, root: &str) -> Option<PathBuf> {
+        let v8_root_dir = V8Dir::from_path(root.into(), DiscoverySource::DefaultPaths);
+        V8CommonDir::find(env, &v8_root_dir).map(|common_dir| common_dir.path)
+    }
+
+    #[test]
+    fn common_dir_inside_or_next_to_root() {
+        let env = MemoryEnvironment::new("linux")
+            .with_file("/opt/1cv8/common/1cestart", "")
+            .with_dir("/opt/1cv8/x86_64/8.3.22.1709")
+            .with_dir("/srv/1c/8.3.22.1709");
+        assert_eq!(find(&env, "/opt/1cv8/x86_64"), Some(PathBuf::from("/opt/1cv8/common")));
+        assert_eq!(find(&env, "/opt/1cv8"), Some(PathBuf::from("/opt/1cv8/common")));
+        assert_eq!(find(&env, "/srv/1c"), None);
+
+        // В Windows каталог common ищется только внутри корневого каталога
+        let env = MemoryEnvironment::new("windows")
+            .with_file(r"C:\Program Files\1cv8\common\1cestart.exe", "")
+            .with_dir(r"C:\Program Files\1cv8\8.3.22.1709\bin");
+        assert_eq!(find(&env, r"C:\Program Files\1cv8"), Some(PathBuf::from(r"C:\Program Files\1cv8").join("common")));
+        assert_eq!(find(&env, r"C:\Program Files\1cv8\8.3.22.1709"), None);
+    }
+
+    #[test]
+    fn starter_path_depends_on_os() {
+        let common_dir = |path: &str| V8CommonDir {
+            path: path.into(),
+            root: PathBuf::new(),
+            source: DiscoverySource::DefaultPaths,
+        };
+        let env = MemoryEnvironment::new("macos")
+            .with_file("/Applications/1cv8/common/1cestart.app/Contents/MacOS/1cestart", "");
+        assert_eq!(common_dir("/Applications/1cv8/common").starter_path(&env),
+                   Some(PathBuf::from("/Applications/1cv8/common/1cestart.app/Contents/MacOS/1cestart")));
+
+        let env = MemoryEnvironment::new("linux").with_dir("/opt/1cv8/common");
+        assert_eq!(common_dir("/opt/1cv8/common").starter_path(&env), None);
+    }
+}
@@ -1,16 +1,19 @@
 use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
 
+use crate::v8_finder::v8_discovery::DiscoverySource;
 
 #[derive(Debug, Clone)]
 pub struct V8Dir {
     pub path: PathBuf,
+    pub source: DiscoverySource,
 }
 
 impl V8Dir {
-    pub fn from_path(path: PathBuf) -> Self {
+    pub fn from_path(path: PathBuf, source: DiscoverySource) -> Self {
         V8Dir {
             path,
+            source,
         }
     }
 }
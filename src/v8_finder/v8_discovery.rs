@@ -0,0 +1,66 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::v8_error::V8FindError;
+
+/// Источник, из которого были получены каталоги установки платформы
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DiscoverySource {
+    /// 1CEStart.cfg для всех пользователей
+    AllUsersStarter,
+    /// 1CEStart.cfg для текущего пользователя
+    CurrentUserStarter,
+    /// Стандартные пути установки платформы
+    DefaultPaths,
+    /// Каталоги, явно переданные в [V8FinderBuilder](crate::v8_finder::V8FinderBuilder)
+    CustomRoots,
+    /// Пользовательский [PlatformSource](crate::v8_finder::PlatformSource) с указанным именем
+    Custom(String),
+    /// Платформа создана через [V8Platform::new](crate::v8_finder::V8Platform::new), например, для передачи
+    /// в [V8FinderBuilder::platforms](crate::v8_finder::V8FinderBuilder::platforms)
+    Explicit,
+}
+
+impl fmt::Display for DiscoverySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiscoverySource::AllUsersStarter => write!(f, "1CEStart.cfg for all users"),
+            DiscoverySource::CurrentUserStarter => write!(f, "1CEStart.cfg for current user"),
+            DiscoverySource::DefaultPaths => write!(f, "default installation paths"),
+            DiscoverySource::CustomRoots => write!(f, "custom roots"),
+            DiscoverySource::Custom(name) => write!(f, "{}", name),
+            DiscoverySource::Explicit => write!(f, "explicit platforms"),
+        }
+    }
+}
+
+/// Проблема, возникшая при опросе одного из источников. Поиск при этом не прерывается:
+/// платформы из остальных источников остаются доступны
+#[derive(Debug)]
+pub struct DiscoveryIssue {
+    /// Источник, при опросе которого возникла проблема
+    pub source: DiscoverySource,
+    /// Путь к файлу или каталогу, если он известен
+    pub path: Option<PathBuf>,
+    /// Исходная ошибка
+    pub error: V8FindError,
+}
+
+impl DiscoveryIssue {
+    /// Создание записи о проблеме. Используется в собственных реализациях
+    /// [PlatformSource](crate::v8_finder::PlatformSource)
+    pub fn new(source: DiscoverySource, path: Option<PathBuf>, error: V8FindError) -> Self {
+        DiscoveryIssue { source, path, error }
+    }
+}
+
+impl fmt::Display for DiscoveryIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{} ({}): {}", self.source, path.display(), self.error),
+            None => write!(f, "{}: {}", self.source, self.error),
+        }
+    }
+}
+
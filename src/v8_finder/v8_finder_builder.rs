@@ -0,0 +1,369 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use itertools::Itertools;
+
+use crate::v8_env::{Environment, SystemEnvironment};
+use crate::v8_error::V8FindError;
+#[cfg(feature = "cache")]
+use crate::v8_finder::v8_cache::CacheLocation;
+use crate::v8_finder::v8_platform::V8Platform;
+use crate::v8_finder::v8_source::{DefaultPathsSource, PlatformSource, RootsSource, StarterConfigSource};
+use crate::v8_finder::V8Finder;
+
+/// Построитель [V8Finder] для поиска с нестандартными настройками.
+/// Пример использования:
+///```no_run
+/// use v8find4rs::v8_finder::{V8Finder, SearchPriority};
+///
+/// let finder = V8Finder::builder()
+///     .no_defaults()
+///     .root("/srv/1c/platforms")
+///     .build();
+/// let platform = finder.get_platform("8.3", SearchPriority::X64);
+/// ```
+pub struct V8FinderBuilder {
+    starter_configs: bool,
+    default_paths: bool,
+    extra_roots: Vec<PathBuf>,
+    excluded_roots: Vec<PathBuf>,
+    sources: Vec<Box<dyn PlatformSource>>,
+    platforms: Vec<V8Platform>,
+    env: Arc<dyn Environment>,
+    #[cfg(feature = "cache")]
+    cache: Option<CacheLocation>,
+}
+
+impl V8FinderBuilder {
+    /// Создание построителя с настройками по умолчанию, аналогичными [V8Finder::new]
+    pub fn new() -> Self {
+        V8FinderBuilder {
+            starter_configs: true,
+            default_paths: true,
+            extra_roots: Vec::new(),
+            excluded_roots: Vec::new(),
+            sources: Vec::new(),
+            platforms: Vec::new(),
+            env: Arc::new(SystemEnvironment),
+            #[cfg(feature = "cache")]
+            cache: None,
+        }
+    }
+
+    /// Добавляет каталог, подкаталоги которого будут проверены на наличие версий платформы
+    /// наравне со стандартными путями установки
+    pub fn root<P: Into<PathBuf>>(mut self, root: P) -> Self {
+        self.extra_roots.push(root.into());
+        self
+    }
+
+    /// Добавляет несколько каталогов, см. [V8FinderBuilder::root]
+    pub fn roots<I, P>(mut self, roots: I) -> Self
+        where I: IntoIterator<Item=P>,
+              P: Into<PathBuf> {
+        self.extra_roots.extend(roots.into_iter().map(Into::into));
+        self
+    }
+
+    /// Исключает каталог из поиска, даже если он указан в 1CEStart.cfg или входит в стандартные пути
+    pub fn exclude_root<P: Into<PathBuf>>(mut self, root: P) -> Self {
+        self.excluded_roots.push(root.into());
+        self
+    }
+
+    /// Включает или отключает чтение каталогов из файлов 1CEStart.cfg
+    pub fn starter_configs(mut self, enabled: bool) -> Self {
+        self.starter_configs = enabled;
+        self
+    }
+
+    /// Включает или отключает поиск в стандартных путях установки платформы
+    pub fn default_paths(mut self, enabled: bool) -> Self {
+        self.default_paths = enabled;
+        self
+    }
+
+    /// Отключает и 1CEStart.cfg, и стандартные пути установки. Поиск будет выполняться только в каталогах,
+    /// переданных через [V8FinderBuilder::root], и в источниках, добавленных через [V8FinderBuilder::source]
+    pub fn no_defaults(self) -> Self {
+        self.starter_configs(false).default_paths(false)
+    }
+
+    /// Добавляет собственный источник каталогов установки или платформ. Опрашивается после встроенных
+    pub fn source<S: PlatformSource + 'static>(mut self, source: S) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// Задает окружение, в котором выполняется поиск. По умолчанию используется [SystemEnvironment];
+    /// [MemoryEnvironment](crate::v8_env::MemoryEnvironment) позволяет проверить поиск для другой ОС
+    pub fn environment<E: Environment + 'static>(mut self, env: E) -> Self {
+        self.env = Arc::new(env);
+        self
+    }
+
+    /// Добавляет готовые описания платформ к найденным на диске. Вместе с [V8FinderBuilder::no_defaults]
+    /// позволяет получить [V8Finder] без обращения к диску, например, в тестах
+    pub fn platforms(mut self, platforms: Vec<V8Platform>) -> Self {
+        self.platforms.extend(platforms);
+        self
+    }
+
+    /// Сохраняет результат поиска в указанный файл и использует его при следующих запусках, пока
+    /// не изменятся файлы 1CEStart.cfg и корневые каталоги установки (по времени изменения). Время
+    /// изменения корневого каталога меняется при установке и удалении версий, но не при замене файлов
+    /// внутри каталога версии: для этого следует вызвать [V8Finder::refresh]. При сохранении сведения
+    /// о платформах, которые обычно определяются лениво, определяются сразу
+    ///```no_run
+    /// use v8find4rs::v8_finder::V8Finder;
+    ///
+    /// let finder = V8Finder::builder()
+    ///     .cache("/var/cache/build-agent/v8find.json")
+    ///     .build();
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn cache<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.cache = Some(CacheLocation::Path(path.into()));
+        self
+    }
+
+    /// Аналог [V8FinderBuilder::cache] с файлом в каталоге кэша текущего пользователя:
+    /// `%LOCALAPPDATA%\v8find4rs` в Windows, `~/Library/Caches/v8find4rs` в macOS,
+    /// `$XDG_CACHE_HOME/v8find4rs` или `~/.cache/v8find4rs` в Linux
+    #[cfg(feature = "cache")]
+    pub fn default_cache(mut self) -> Self {
+        self.cache = Some(CacheLocation::Default);
+        self
+    }
+
+    /// Выполняет поиск и возвращает [V8Finder]. Проблемы с отдельными источниками записываются в канал `warn`
+    /// и доступны через [V8Finder::issues]
+    pub fn build(self) -> V8Finder {
+        let mut sources: Vec<Box<dyn PlatformSource>> = Vec::new();
+        if self.starter_configs {
+            sources.push(Box::new(StarterConfigSource::all_users()));
+            sources.push(Box::new(StarterConfigSource::current_user()));
+        }
+        if self.default_paths {
+            sources.push(Box::new(DefaultPathsSource));
+        }
+        if !self.extra_roots.is_empty() {
+            sources.push(Box::new(RootsSource::new(self.extra_roots)));
+        }
+        sources.extend(self.sources);
+
+        #[cfg(feature = "cache")]
+        let cache = match self.cache {
+            Some(cache) => cache.resolve(self.env.as_ref()),
+            None => None,
+        };
+        V8Finder::discover(Arc::new(V8FinderConfig {
+            env: self.env,
+            sources,
+            excluded_roots: self.excluded_roots,
+            platforms: self.platforms,
+            #[cfg(feature = "cache")]
+            cache,
+        }))
+    }
+
+    /// Аналог [V8FinderBuilder::build] с возвратом ошибки по правилам [V8Finder::try_new]
+    pub fn try_build(self) -> Result<V8Finder, V8FindError> {
+        self.build().into_result()
+    }
+}
+
+/// Настройки, с которыми был выполнен поиск. Хранятся в [V8Finder], чтобы поиск можно было повторить
+pub(crate) struct V8FinderConfig {
+    pub(crate) env: Arc<dyn Environment>,
+    pub(crate) sources: Vec<Box<dyn PlatformSource>>,
+    pub(crate) excluded_roots: Vec<PathBuf>,
+    pub(crate) platforms: Vec<V8Platform>,
+    #[cfg(feature = "cache")]
+    pub(crate) cache: Option<PathBuf>,
+}
+
+impl V8FinderConfig {
+    /// Файлы настроек источников (1CEStart.cfg), от которых зависит результат поиска
+    pub(crate) fn config_files(&self) -> Vec<PathBuf> {
+        self.sources.iter()
+            .flat_map(|source| source.config_files(self.env.as_ref()))
+            .unique()
+            .collect()
+    }
+}
+
+impl Default for V8FinderBuilder {
+    fn default() -> Self {
+        V8FinderBuilder::new()
+    }
+}
+
+impl fmt::Debug for V8FinderBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sources: Vec<_> = self.sources.iter().map(|source| source.source()).collect();
+        let mut debug = f.debug_struct("V8FinderBuilder");
+        debug.field("starter_configs", &self.starter_configs)
+            .field("default_paths", &self.default_paths)
+            .field("extra_roots", &self.extra_roots)
+            .field("excluded_roots", &self.excluded_roots)
+            .field("sources", &sources)
+            .field("platforms", &self.platforms)
+            .field("os", &self.env.os());
+        #[cfg(feature = "cache")]
+        debug.field("cache", &self.cache);
+        debug.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+    use std::path::Path;
+
+    use super::*;
+    use crate::v8_app::v8_binary::tests::elf;
+    use crate::v8_app::{V8Arch, V8Component, V8Edition};
+    use crate::v8_env::MemoryEnvironment;
+    use crate::v8_finder::{Candidate, DiscoveryIssue, DiscoverySource, SearchPriority};
+
+    fn linux() -> MemoryEnvironment {
+        MemoryEnvironment::new("linux")
+            .with_home("/home/user")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/1cv8", elf(2, 1, 62))
+            .with_file("/etc/1C/1cestart/1cestart.cfg", "InstalledLocation=/srv/1c\n")
+            .with_file("/srv/1c/8.3.23.1865/1cv8", elf(2, 1, 62))
+            .with_file("/mnt/1c/8.3.24.1342/1cv8", elf(2, 1, 62))
+    }
+
+    fn versions(finder: &V8Finder) -> Vec<String> {
+        finder.platforms().iter().map(|platform| platform.version().to_string()).collect()
+    }
+
+    #[test]
+    fn default_sources_and_extra_roots() {
+        let finder = V8FinderBuilder::new().environment(linux()).root("/mnt/1c").build();
+
+        assert!(finder.issues().is_empty(), "{:?}", finder.issues());
+        assert_eq!(versions(&finder), ["8.3.23.1865", "8.3.22.1709", "8.3.24.1342"]);
+        assert_eq!(finder.platforms()[2].source(), &DiscoverySource::CustomRoots);
+    }
+
+    #[test]
+    fn disabled_default_sources() {
+        let without_configs = V8FinderBuilder::new().environment(linux()).starter_configs(false).build();
+        assert_eq!(versions(&without_configs), ["8.3.22.1709"]);
+
+        let without_default_paths = V8FinderBuilder::new().environment(linux()).default_paths(false).build();
+        assert_eq!(versions(&without_default_paths), ["8.3.23.1865"]);
+
+        let only_roots = V8FinderBuilder::new().environment(linux()).no_defaults().root("/mnt/1c").build();
+        assert_eq!(versions(&only_roots), ["8.3.24.1342"]);
+        assert!(V8FinderBuilder::new().environment(linux()).no_defaults().build().platforms().is_empty());
+    }
+
+    #[test]
+    fn excluded_roots_are_not_read() {
+        let finder = V8FinderBuilder::new()
+            .environment(linux())
+            .root("/mnt/1c")
+            .exclude_root("/srv/1c")
+            .exclude_root("/opt/1cv8/x86_64")
+            .build();
+
+        assert_eq!(versions(&finder), ["8.3.24.1342"]);
+        assert!(finder.roots().iter().all(|root| root != Path::new("/srv/1c")));
+    }
+
+    #[test]
+    fn injected_platforms_follow_discovered_ones() {
+        let injected = V8Platform::new("8.3.25.1000".parse().unwrap(), V8Arch::X64, "/virtual/8.3.25.1000".into())
+            .with_components(iter::once(V8Component::ThickClient).collect())
+            .with_edition(V8Edition::Full);
+        let finder = V8FinderBuilder::new()
+            .environment(linux())
+            .no_defaults()
+            .root("/mnt/1c")
+            .platforms(vec![injected])
+            .build();
+
+        assert_eq!(versions(&finder), ["8.3.24.1342", "8.3.25.1000"]);
+        let platform = &finder.platforms()[1];
+        assert_eq!(platform.source(), &DiscoverySource::Explicit);
+        assert_eq!(platform.root(), Path::new("/virtual"));
+        let latest = finder.get_platform("8.3", SearchPriority::X64).unwrap();
+        assert_eq!(latest.path(), Path::new("/virtual/8.3.25.1000"));
+    }
+
+    #[test]
+    fn injected_platforms_without_components() {
+        let injected = V8Platform::new("8.3.25.1000".parse().unwrap(), V8Arch::X64, "/virtual/8.3.25.1000".into());
+        let finder = V8FinderBuilder::new()
+            .environment(linux())
+            .no_defaults()
+            .platforms(vec![injected])
+            .build();
+
+        let platform = finder.get_platform("8.3.25", SearchPriority::X64).unwrap();
+        assert_eq!(platform.path(), Path::new("/virtual/8.3.25.1000"));
+        assert!(platform.components().is_empty());
+        assert_eq!(platform.edition(), V8Edition::Full);
+    }
+
+    // Возвращает каталог и готовую платформу, а каталог из переменной окружения - только если она задана
+    struct NasMirror;
+
+    impl PlatformSource for NasMirror {
+        fn source(&self) -> DiscoverySource {
+            DiscoverySource::Custom("nas mirror".to_owned())
+        }
+
+        fn discover(&self, env: &dyn Environment, issues: &mut Vec<DiscoveryIssue>) -> Vec<Candidate> {
+            let mut candidates = vec![
+                Candidate::Root("/mnt/1c".into()),
+                Candidate::Platform(V8Platform::new("8.3.20.2290".parse().unwrap(), V8Arch::X86,
+                                                    "/mnt/archive/8.3.20.2290".into())
+                    .with_source(self.source())
+                    .with_edition(V8Edition::Full)),
+            ];
+            match env.var_os("NAS_MIRROR") {
+                Some(root) => candidates.push(Candidate::Root(root.into())),
+                None => issues.push(DiscoveryIssue::new(self.source(), None,
+                                                        V8FindError::EnvVar { name: "NAS_MIRROR".to_owned() })),
+            }
+            candidates
+        }
+
+        fn config_files(&self, _env: &dyn Environment) -> Vec<PathBuf> {
+            vec![PathBuf::from("/etc/nas-mirror.conf")]
+        }
+    }
+
+    #[test]
+    fn custom_source_after_builtin_ones() {
+        let env = linux()
+            .with_var("NAS_MIRROR", "/mnt/nas")
+            .with_file("/mnt/nas/8.3.21.1895/1cv8", elf(2, 1, 62));
+        let finder = V8FinderBuilder::new().environment(env).starter_configs(false).source(NasMirror).build();
+
+        assert!(finder.issues().is_empty(), "{:?}", finder.issues());
+        // Готовые платформы из источников предшествуют найденным в каталогах
+        assert_eq!(versions(&finder), ["8.3.20.2290", "8.3.22.1709", "8.3.24.1342", "8.3.21.1895"]);
+        let custom = DiscoverySource::Custom("nas mirror".to_owned());
+        assert!(finder.platforms().iter().skip(2).all(|platform| platform.source() == &custom));
+        assert_eq!(finder.platforms()[0].source(), &custom);
+        assert_eq!(finder.get_platform("8.3", SearchPriority::X32).unwrap().version().to_string(), "8.3.20.2290");
+        assert_eq!(finder.config_files(), [PathBuf::from("/etc/nas-mirror.conf")]);
+    }
+
+    #[test]
+    fn custom_source_issues_are_reported() {
+        let finder = V8FinderBuilder::new().environment(linux()).no_defaults().source(NasMirror).build();
+
+        assert_eq!(versions(&finder), ["8.3.20.2290", "8.3.24.1342"]);
+        assert_eq!(finder.issues().len(), 1);
+        assert_eq!(finder.issues()[0].source, DiscoverySource::Custom("nas mirror".to_owned()));
+        assert!(matches!(&finder.issues()[0].error, V8FindError::EnvVar { name } if name == "NAS_MIRROR"));
+    }
+}
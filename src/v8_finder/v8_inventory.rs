@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::v8_env::{Environment, MemoryEnvironment};
+use crate::v8_finder::v8_platform::V8Platform;
+use crate::v8_finder::v8_finder_builder::V8FinderConfig;
+use crate::v8_finder::V8Finder;
+
+// Вместе с платформами сохраняются ОС и архитектура компьютера, на котором выполнялся поиск, чтобы
+// SearchPriority::Native выбирал ту же платформу, что и на исходном компьютере
+#[derive(Serialize)]
+struct InventoryRef<'a> {
+    os: &'a str,
+    arch: &'a str,
+    platforms: &'a [V8Platform],
+}
+
+#[derive(Deserialize)]
+struct Inventory {
+    os: String,
+    arch: String,
+    platforms: Vec<V8Platform>,
+}
+
+impl Serialize for V8Finder {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        InventoryRef {
+            os: self.env.os(),
+            arch: self.env.arch(),
+            platforms: &self.platforms,
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for V8Finder {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let inventory = Inventory::deserialize(deserializer)?;
+        let env: Arc<dyn Environment> = Arc::new(MemoryEnvironment::new(&inventory.os).with_arch(&inventory.arch));
+        Ok(V8Finder::discover(Arc::new(V8FinderConfig {
+            env,
+            sources: Vec::new(),
+            excluded_roots: Vec::new(),
+            platforms: inventory.platforms,
+            #[cfg(feature = "cache")]
+            cache: None,
+        })))
+    }
+}
+
+impl V8Finder {
+    /// Выгружает найденные платформы в JSON: версию, архитектуру и способ ее определения, вариант поставки,
+    /// каталоги, источник и состав компонентов каждой платформы, а также ОС и архитектуру компьютера.
+    /// Все сведения, которые определяются лениво, при выгрузке определяются сразу. Исключение - версия
+    /// из метаданных ([V8Platform::product_version]): она выгружается, только если уже была прочитана
+    ///```no_run
+    /// use v8find4rs::v8_finder::{SearchPriority, V8Finder};
+    ///
+    /// let json = V8Finder::new().to_json().unwrap();
+    /// let inventory = V8Finder::from_json(&json).unwrap();
+    /// let platform = inventory.get_platform("8.3", SearchPriority::X64);
+    /// ```
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Восстанавливает [V8Finder] из JSON, полученного через [V8Finder::to_json]. Восстановленный
+    /// экземпляр к диску не обращается, а [V8Finder::get_platform] выбирает платформы так же,
+    /// как на компьютере, где выполнялся поиск
+    pub fn from_json(json: &str) -> serde_json::Result<V8Finder> {
+        serde_json::from_str(json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::v8_app::v8_binary::tests::elf;
+    use crate::v8_app::V8Edition;
+    use crate::v8_finder::SearchPriority;
+    use crate::v8_version::{V8Version, V8VersionReq};
+
+    fn linux() -> MemoryEnvironment {
+        MemoryEnvironment::new("linux")
+            .with_home("/home/user")
+            .with_arch("x86_64")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/1cv8", elf(2, 1, 62))
+            .with_file("/opt/1cv8/x86_64/8.3.23.1865/1cv8c", elf(2, 1, 62))
+            .with_file("/opt/1cv8/aarch64/8.3.24.1342/1cv8", elf(2, 1, 183))
+            .with_file("/opt/1cv8t/x86_64/8.3.25.1000/1cv8", elf(2, 1, 62))
+            .with_file("/var/lib/dpkg/status", "Package: 1c-enterprise83-client\n\
+                Status: install ok installed\n\
+                Version: 8.3.22-1709\n")
+            .with_file("/var/lib/dpkg/info/1c-enterprise83-client.list", "/opt/1cv8/x86_64/8.3.22.1709\n")
+    }
+
+    fn product_versions(json: &str) -> usize {
+        let inventory: serde_json::Value = serde_json::from_str(json).unwrap();
+        inventory["platforms"].as_array().unwrap().iter()
+            .filter(|platform| platform.get("product_version").is_some())
+            .count()
+    }
+
+    #[test]
+    fn round_trip_keeps_platform_selection() {
+        let finder = V8Finder::builder().environment(linux()).build();
+        let json = finder.to_json().unwrap();
+        // Версия из метаданных не читается ради выгрузки
+        assert_eq!(product_versions(&json), 0);
+
+        let inventory = V8Finder::from_json(&json).unwrap();
+        let versions = |finder: &V8Finder| finder.platforms().iter()
+            .map(|platform| (platform.version(), platform.arch(), platform.edition()))
+            .collect::<Vec<_>>();
+        assert_eq!(versions(&inventory), versions(&finder));
+
+        let native = inventory.get_platform("8.3", SearchPriority::Native).unwrap();
+        assert_eq!(native.version(), V8Version::new(8, 3, 23, 1865));
+        assert_eq!(native.edition(), V8Edition::ThinClientOnly);
+        assert_eq!(native, finder.get_platform("8.3", SearchPriority::Native).unwrap());
+        assert_eq!(inventory.get_platform("8.3.25", SearchPriority::Native), None);
+
+        let any = V8VersionReq::any();
+        let full = inventory.find_platform_by_edition(&any, SearchPriority::Native, &[V8Edition::Full]).unwrap();
+        assert_eq!(full.version(), V8Version::new(8, 3, 22, 1709));
+        let training = inventory.find_platform_by_edition(&any, SearchPriority::Native, &[V8Edition::Training]);
+        assert_eq!(training.unwrap().version(), V8Version::new(8, 3, 25, 1000));
+    }
+
+    #[test]
+    fn product_version_is_exported_once_read() {
+        let finder = V8Finder::builder().environment(linux()).build();
+        let platform = finder.get_platform("8.3.22", SearchPriority::X64).unwrap();
+        assert_eq!(platform.product_version(), Some(V8Version::new(8, 3, 22, 1709)));
+
+        let json = finder.to_json().unwrap();
+        assert_eq!(product_versions(&json), 1);
+        let inventory = V8Finder::from_json(&json).unwrap();
+        let imported = inventory.get_platform("8.3.22", SearchPriority::X64).unwrap();
+        assert_eq!(imported.product_version(), Some(V8Version::new(8, 3, 22, 1709)));
+    }
+}
@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+use std::iter;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use log::warn;
+use md5::{Digest, Md5};
+
+use crate::v8_app::v8_binary;
+use crate::v8_env::Environment;
+use crate::v8_error::V8FindError;
+use crate::v8_version::V8Version;
+
+const WINDOWS_BINARIES: &[&str] = &["bin/1cv8.exe", "bin/1cv8c.exe", "bin/1cv8s.exe", "bin/ragent.exe"];
+const LINUX_BINARIES: &[&str] = &["1cv8", "1cv8c", "1cv8s", "ragent"];
+const MACOS_BUNDLES: &[&str] = &["1cv8.app", "1cv8c.app"];
+const DPKG_STATUS: &str = "/var/lib/dpkg/status";
+const DPKG_INFO: &str = "/var/lib/dpkg/info";
+const PACKAGE_PREFIX: &str = "1c-enterprise";
+
+/// Читает версию платформы из метаданных установленных файлов, а не из имени каталога:
+/// * Windows - из ресурса VS_VERSIONINFO первого найденного из файлов 1cv8.exe, 1cv8c.exe, 1cv8s.exe,
+///   ragent.exe в папке bin;
+/// * Linux - из базы dpkg: версия пакета 1c-enterprise*, которому принадлежит каталог платформы. Если каталога
+///   нет в списках файлов пакетов (он скопирован или переименован), пакет ищется по контрольной сумме MD5 первого
+///   найденного из файлов 1cv8, 1cv8c, 1cv8s, ragent в файлах `*.md5sums`. База rpm не читается;
+/// * macOS - из `CFBundleVersion` файла Info.plist приложения 1cv8.app или 1cv8c.app.
+///
+/// `None`, если метаданные отсутствуют или их не удалось прочитать
+pub(crate) fn read_product_version(env: &dyn Environment, packages: &PackageIndex, v8_path: &Path) -> Option<V8Version> {
+    match env.os() {
+        "windows" => version_from_binaries(env, v8_path),
+        "linux" => packages.dpkg_version(env, v8_path),
+        "macos" => version_from_bundles(env, v8_path),
+        _ => None,
+    }
+}
+
+/// Читает версию отдельного исполняемого файла: из ресурса VS_VERSIONINFO в Windows, из версии пакета dpkg,
+/// которому принадлежит файл, в Linux, из Info.plist приложения, внутри которого находится файл, в macOS
+pub(crate) fn read_file_version(env: &dyn Environment, packages: &PackageIndex,
+                                path_to_binary: &Path) -> Option<V8Version> {
+    match env.os() {
+        "windows" => read_exe_version(env, path_to_binary)
+            .map_err(|err| warn!("{}", err))
+            .ok(),
+        "linux" => packages.dpkg_version(env, path_to_binary),
+        "macos" => {
+            let bundle = path_to_binary.ancestors()
+                .find(|ancestor| ancestor.extension().is_some_and(|extension| extension == "app"))?;
+            let plist = env.read(&bundle.join("Contents").join("Info.plist")).ok()?;
+            plist_string_value(&String::from_utf8_lossy(&plist), "CFBundleVersion")?.parse().ok()
+        }
+        _ => None,
+    }
+}
+
+fn version_from_binaries(env: &dyn Environment, v8_path: &Path) -> Option<V8Version> {
+    WINDOWS_BINARIES.iter()
+        .map(|binary| v8_path.join(binary))
+        .filter(|binary_path| env.exists(binary_path))
+        .find_map(|binary_path| match read_exe_version(env, &binary_path) {
+            Ok(version) => Some(version),
+            Err(err) => {
+                warn!("{}", err);
+                None
+            }
+        })
+}
+
+fn read_exe_version(env: &dyn Environment, path_to_exe: &Path) -> Result<V8Version, V8FindError> {
+    let buf = env.read(path_to_exe)
+        .map_err(|source| V8FindError::Io { path: path_to_exe.to_owned(), source })?;
+
+    v8_binary::version_from_pe(&buf)
+        .map_err(|message| V8FindError::PeParse { path: path_to_exe.to_owned(), message })
+}
+
+/// Файлы пакетов 1С из базы dpkg с версиями пакетов, которым они принадлежат. Общий для всех платформ
+/// одного поиска: база читается и разбирается один раз, при первом обращении к версии любой из платформ
+#[derive(Debug, Default)]
+pub(crate) struct PackageIndex {
+    dpkg: OnceLock<DpkgIndex>,
+}
+
+#[derive(Debug, Default)]
+struct DpkgIndex {
+    // Пути файлов и каталогов из списков *.list
+    files: HashMap<String, V8Version>,
+    // Контрольные суммы MD5 из *.md5sums. None - одинаковый файл входит в пакеты разных версий
+    checksums: HashMap<String, Option<V8Version>>,
+}
+
+impl PackageIndex {
+    // Скопированного или переименованного каталога нет в списках файлов пакетов, но исполняемые файлы
+    // в нем те же, что и в пакете, поэтому пакет находится по их контрольной сумме
+    fn dpkg_version(&self, env: &dyn Environment, path: &Path) -> Option<V8Version> {
+        let index = self.dpkg.get_or_init(|| read_dpkg_index(env));
+        if let Some(version) = index.files.get(path.to_str()?.trim_end_matches('/')) {
+            return Some(*version);
+        }
+        if index.checksums.is_empty() {
+            return None;
+        }
+
+        LINUX_BINARIES.iter()
+            .map(|binary| path.join(binary))
+            .chain(iter::once(path.to_path_buf()))
+            .filter(|file| env.exists(file))
+            .find_map(|file| env.read(&file).ok())
+            .and_then(|data| *index.checksums.get(&format!("{:x}", Md5::digest(&data)))?)
+    }
+}
+
+// Пакеты 1С называются 1c-enterprise83-server (версия 8.3.22-1709) или 1c-enterprise-8.3.22.1709-server.
+// Каталог или файл относится к пакету, если он перечислен в списке файлов пакета
+fn read_dpkg_index(env: &dyn Environment) -> DpkgIndex {
+    let mut index = DpkgIndex::default();
+    let status = match env.read(Path::new(DPKG_STATUS)) {
+        Ok(status) => status,
+        Err(_) => return index,
+    };
+    let status = String::from_utf8_lossy(&status);
+
+    let packages = status.split("\n\n")
+        .filter_map(DpkgPackage::parse)
+        .filter(|package| package.name.starts_with(PACKAGE_PREFIX) && package.installed);
+    for package in packages {
+        let version = match package.version.parse() {
+            Ok(version) => version,
+            Err(_) => continue,
+        };
+        for file in package.files(env) {
+            index.files.entry(file).or_insert(version);
+        }
+        for checksum in package.checksums(env) {
+            let package_version = index.checksums.entry(checksum).or_insert(Some(version));
+            if *package_version != Some(version) {
+                *package_version = None;
+            }
+        }
+    }
+    index
+}
+
+struct DpkgPackage<'a> {
+    name: &'a str,
+    version: &'a str,
+    architecture: Option<&'a str>,
+    installed: bool,
+}
+
+impl<'a> DpkgPackage<'a> {
+    fn parse(paragraph: &'a str) -> Option<Self> {
+        let mut name = None;
+        let mut version = None;
+        let mut architecture = None;
+        let mut installed = false;
+        for line in paragraph.lines() {
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) if !key.starts_with(' ') => (key, value.trim()),
+                _ => continue,
+            };
+            match key {
+                "Package" => name = Some(value),
+                "Version" => version = Some(value),
+                "Architecture" => architecture = Some(value),
+                "Status" => installed = value.ends_with(" installed"),
+                _ => {}
+            }
+        }
+
+        Some(DpkgPackage { name: name?, version: version?, architecture, installed })
+    }
+
+    fn files(&self, env: &dyn Environment) -> Vec<String> {
+        self.info_lines(env, "list").iter()
+            .map(|line| line.trim_end_matches('/').to_owned())
+            .collect()
+    }
+
+    // Строки вида `<md5>  opt/1cv8/x86_64/8.3.22.1709/1cv8`, нужны только суммы
+    fn checksums(&self, env: &dyn Environment) -> Vec<String> {
+        self.info_lines(env, "md5sums").iter()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(str::to_ascii_lowercase)
+            .collect()
+    }
+
+    // Файлы пакета в /var/lib/dpkg/info называются по имени пакета, для пакетов с архитектурой - еще и с ней
+    fn info_lines(&self, env: &dyn Environment, extension: &str) -> Vec<String> {
+        let mut info_names = vec![format!("{}.{}", self.name, extension)];
+        if let Some(architecture) = self.architecture {
+            info_names.push(format!("{}:{}.{}", self.name, architecture, extension));
+        }
+
+        info_names.iter()
+            .filter_map(|info_name| env.read(&Path::new(DPKG_INFO).join(info_name)).ok())
+            .flat_map(|info| String::from_utf8_lossy(&info).lines().map(str::to_owned).collect::<Vec<_>>())
+            .collect()
+    }
+}
+
+fn version_from_bundles(env: &dyn Environment, v8_path: &Path) -> Option<V8Version> {
+    MACOS_BUNDLES.iter()
+        .map(|bundle| v8_path.join(bundle).join("Contents").join("Info.plist"))
+        .filter_map(|plist_path| env.read(&plist_path).ok())
+        .find_map(|plist| plist_string_value(&String::from_utf8_lossy(&plist), "CFBundleVersion")?.parse().ok())
+}
+
+// Достаточно для XML варианта Info.plist: значение следует сразу за ключом
+fn plist_string_value<'a>(plist: &'a str, key: &str) -> Option<&'a str> {
+    let key_tag = format!("<key>{}</key>", key);
+    let after_key = &plist[plist.find(&key_tag)? + key_tag.len()..];
+    let value = after_key.trim_start().strip_prefix("<string>")?;
+    Some(value[..value.find("</string>")?].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+    use std::io;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::v8_env::MemoryEnvironment;
+    use crate::v8_finder::V8Finder;
+
+    // Считает чтения базы dpkg
+    struct CountingEnvironment {
+        env: MemoryEnvironment,
+        status_reads: Arc<AtomicUsize>,
+    }
+
+    impl Environment for CountingEnvironment {
+        fn os(&self) -> &str {
+            self.env.os()
+        }
+
+        fn var_os(&self, name: &str) -> Option<OsString> {
+            self.env.var_os(name)
+        }
+
+        fn home_dir(&self) -> Option<PathBuf> {
+            self.env.home_dir()
+        }
+
+        fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+            if path == Path::new(DPKG_STATUS) {
+                self.status_reads.fetch_add(1, Ordering::SeqCst);
+            }
+            self.env.read(path)
+        }
+
+        fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+            self.env.read_dir(path)
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            self.env.exists(path)
+        }
+    }
+
+    const STATUS: &str = "Package: 1c-enterprise83-common\n\
+        Status: install ok installed\n\
+        Architecture: amd64\n\
+        Version: 8.3.22-1709\n\
+        \n\
+        Package: 1c-enterprise-8.3.23.1865-common\n\
+        Status: install ok installed\n\
+        Architecture: amd64\n\
+        Version: 8.3.23-1865\n\
+        \n\
+        Package: 1c-enterprise83-server\n\
+        Status: deinstall ok config-files\n\
+        Architecture: amd64\n\
+        Version: 8.3.21-1895\n\
+        \n\
+        Package: bash\n\
+        Status: install ok installed\n\
+        Version: 5.2-1\n";
+
+    fn linux() -> MemoryEnvironment {
+        MemoryEnvironment::new("linux")
+            .with_file(DPKG_STATUS, STATUS)
+            .with_file("/var/lib/dpkg/info/1c-enterprise83-common:amd64.list",
+                       "/opt\n/opt/1cv8\n/opt/1cv8/x86_64\n/opt/1cv8/x86_64/8.3.22.1709\n/opt/1cv8/x86_64/8.3.22.1709/1cv8s\n")
+            .with_file("/var/lib/dpkg/info/1c-enterprise-8.3.23.1865-common.list",
+                       "/opt/1cv8/x86_64/8.3.23.1865/\n")
+            .with_file("/var/lib/dpkg/info/1c-enterprise83-server:amd64.list", "/opt/1cv8/x86_64/8.3.21.1895\n")
+            .with_dir("/opt/1cv8/x86_64/8.3.22.1709")
+            .with_dir("/opt/1cv8/x86_64/8.3.23.1865")
+            .with_dir("/opt/1cv8/x86_64/8.3.21.1895")
+            .with_dir("/opt/1cv8/x86_64/8.3.24.1342")
+    }
+
+    #[test]
+    fn dpkg_versions() {
+        let env = linux();
+        let packages = PackageIndex::default();
+        let version = |path: &str| read_product_version(&env, &packages, Path::new(path)).map(|v| v.to_string());
+
+        assert_eq!(version("/opt/1cv8/x86_64/8.3.22.1709").as_deref(), Some("8.3.22.1709"));
+        assert_eq!(version("/opt/1cv8/x86_64/8.3.23.1865/").as_deref(), Some("8.3.23.1865"));
+        // Пакет удален, остались только файлы настроек
+        assert_eq!(version("/opt/1cv8/x86_64/8.3.21.1895"), None);
+        assert_eq!(version("/opt/1cv8/x86_64/8.3.24.1342"), None);
+        assert_eq!(read_file_version(&env, &packages, Path::new("/opt/1cv8/x86_64/8.3.22.1709/1cv8s"))
+                       .map(|v| v.to_string()).as_deref(), Some("8.3.22.1709"));
+    }
+
+    #[test]
+    fn dpkg_status_is_read_once_per_scan() {
+        let status_reads = Arc::new(AtomicUsize::new(0));
+        let env = CountingEnvironment { env: linux(), status_reads: Arc::clone(&status_reads) };
+        let mut finder = V8Finder::builder().no_defaults().root("/opt/1cv8/x86_64").environment(env).build();
+        assert_eq!(finder.platforms().len(), 4);
+
+        let read_all = |finder: &V8Finder| finder.platforms().iter()
+            .filter_map(|platform| platform.product_version())
+            .count();
+        assert_eq!(read_all(&finder), 2);
+        assert_eq!(read_all(&finder), 2);
+        assert_eq!(status_reads.load(Ordering::SeqCst), 1);
+
+        finder.refresh();
+        assert_eq!(read_all(&finder), 2);
+        assert_eq!(status_reads.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn dpkg_version_of_copied_platform() {
+        let binary = "1cv8s 8.3.22.1709";
+        let checksum = format!("{:x}", Md5::digest(binary.as_bytes()));
+        let env = linux()
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/1cv8s", binary)
+            .with_file("/var/lib/dpkg/info/1c-enterprise83-common:amd64.md5sums",
+                       format!("{}  opt/1cv8/x86_64/8.3.22.1709/1cv8s\n", checksum.to_uppercase()))
+            .with_file("/srv/1c/8.3.25.1000/1cv8s", binary)
+            .with_file("/srv/1c/8.3.25.1001/1cv8s", "1cv8s 8.3.25.1001");
+        let packages = PackageIndex::default();
+
+        assert_eq!(read_product_version(&env, &packages, Path::new("/srv/1c/8.3.25.1000")),
+                   Some(V8Version::new(8, 3, 22, 1709)));
+        assert_eq!(read_file_version(&env, &packages, Path::new("/srv/1c/8.3.25.1000/1cv8s")),
+                   Some(V8Version::new(8, 3, 22, 1709)));
+        assert_eq!(read_product_version(&env, &packages, Path::new("/srv/1c/8.3.25.1001")), None);
+
+        let finder = V8Finder::builder().no_defaults().root("/srv/1c").environment(env).build();
+        let mismatched: Vec<_> = finder.platforms().iter()
+            .filter(|platform| platform.has_version_mismatch())
+            .map(|platform| platform.version().to_string())
+            .collect();
+        assert_eq!(mismatched, ["8.3.25.1000"]);
+    }
+
+    #[test]
+    fn windows_version_info() {
+        let env = MemoryEnvironment::new("windows")
+            .with_file("C:/Program Files/1cv8/8.3.22.1709/bin/1cv8c.exe",
+                       v8_binary::tests::pe_with_version([8, 3, 22, 1710]));
+        let packages = PackageIndex::default();
+
+        assert_eq!(read_product_version(&env, &packages, Path::new("C:/Program Files/1cv8/8.3.22.1709")),
+                   Some(V8Version::new(8, 3, 22, 1710)));
+        assert_eq!(read_file_version(&env, &packages, Path::new("C:/Program Files/1cv8/8.3.22.1709/bin/1cv8c.exe")),
+                   Some(V8Version::new(8, 3, 22, 1710)));
+        assert_eq!(read_product_version(&env, &packages, Path::new("C:/Program Files/1cv8/8.3.23.1865")), None);
+    }
+
+    #[test]
+    fn macos_bundle_versions() {
+        const INFO_PLIST: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <plist version=\"1.0\">\n\
+            <dict>\n\
+            \t<key>CFBundleShortVersionString</key>\n\
+            \t<string>8.3</string>\n\
+            \t<key>CFBundleVersion</key>\n\
+            \t<string>8.3.22.1709</string>\n\
+            </dict>\n\
+            </plist>\n";
+        let root = "/opt/1cv8/8.3.22.1709";
+        let env = MemoryEnvironment::new("macos")
+            .with_file(format!("{}/1cv8c.app/Contents/Info.plist", root), INFO_PLIST)
+            .with_file(format!("{}/1cv8c.app/Contents/MacOS/1cv8c", root), "")
+            .with_file("/opt/1cv8/8.3.23.1865/1cv8.app/Contents/Info.plist", "<plist><dict></dict></plist>");
+        let packages = PackageIndex::default();
+
+        assert_eq!(read_product_version(&env, &packages, Path::new(root)), Some(V8Version::new(8, 3, 22, 1709)));
+        assert_eq!(read_file_version(&env, &packages, &Path::new(root).join("1cv8c.app/Contents/MacOS/1cv8c")),
+                   Some(V8Version::new(8, 3, 22, 1709)));
+        assert_eq!(read_product_version(&env, &packages, Path::new("/opt/1cv8/8.3.23.1865")), None);
+        assert_eq!(read_file_version(&env, &packages, Path::new("/opt/1cv8/8.3.22.1709/ragent")), None);
+    }
+}
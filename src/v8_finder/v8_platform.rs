@@ -1,38 +1,58 @@
-use crate::v8_app::{V8Arch, V8AppType};
-use std::path::PathBuf;
+use crate::v8_app::{V8Arch, V8AppLayout, V8AppType, V8ArchSource, V8Component, V8Components, V8Edition};
+use std::path::{Path, PathBuf};
 use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::panic;
+use std::sync::{Arc, OnceLock};
+use std::thread;
 use crate::v8_finder::v8_dir::V8Dir;
 use itertools::Itertools;
-use std::{env, io};
-use std::fs::File;
-use std::io::{Read, Error, ErrorKind};
 use regex::Regex;
-use encoding_rs_io::DecodeReaderBytes;
 use lazy_static::lazy_static;
+use crate::v8_version::{ParseVersionError, V8Version};
+use crate::v8_env::{Environment, SystemEnvironment};
+use crate::v8_error::V8FindError;
+use crate::v8_finder::v8_discovery::{DiscoveryIssue, DiscoverySource};
+use crate::v8_finder::v8_metadata::{self, PackageIndex};
+use crate::v8_finder::v8_source::{Candidate, PlatformSource};
+use log::warn;
 
 lazy_static! {
-    static ref PLATFORM_VERSION_REGEX: Regex = Regex::new(r"\d\.\d\.\d+\.\d+").unwrap();
+    // 8.3.22.1709, 8.3.22.1709-x64, 8.3.22.1709_thin
+    static ref PLATFORM_VERSION_REGEX: Regex =
+        Regex::new(r"^(\d+\.\d+\.\d+\.\d+)(?:[-_][0-9A-Za-z_-]+)?$").unwrap();
 }
 
-#[derive(Debug)]
+// Результат чтения одного корневого каталога: сам каталог, найденные платформы и проблемы с подкаталогами
+type RootScan<'a> = (&'a V8Dir, Result<Vec<V8Platform>, V8FindError>, Vec<DiscoveryIssue>);
+
+/// Найденная установленная версия платформы 1С. Архитектура, состав компонентов и вариант поставки
+/// найденных на диске платформ определяются при первом обращении к соответствующим методам,
+/// а не во время поиска.
+///
+/// Две платформы равны, если совпадают их версии и каталоги; в том же порядке они сравниваются
+/// и хешируются. Архитектура и другие определяемые при первом обращении сведения не учитываются,
+/// так что сравнение никогда не обращается к диску
+#[derive(Clone)]
 pub struct V8Platform {
-    // 8.3 <- this .13
-    pub generation: u32,
-    // 8.3.13 <- this
-    pub version: u32,
-    // 8.3.13.1234 <- this
-    pub build: u32,
-    // x64 or x86
-    pub arch: V8Arch,
-    pub path: PathBuf,
+    version: V8Version,
+    path: PathBuf,
+    root: PathBuf,
+    source: DiscoverySource,
+    arch: OnceLock<(V8Arch, V8ArchSource)>,
+    product_version: OnceLock<Option<V8Version>>,
+    components: OnceLock<V8Components>,
+    edition: OnceLock<V8Edition>,
+    env: Option<Arc<dyn Environment>>,
+    // Общий для всех платформ одного поиска
+    packages: Arc<PackageIndex>,
 }
 
 impl PartialEq for V8Platform {
     fn eq(&self, other: &Self) -> bool {
-        self.generation == other.generation
-            && self.version == other.version
-            && self.build == other.build
-            && self.arch == other.arch
+        self.version == other.version
+            && self.path == other.path
     }
 }
 
@@ -46,221 +66,470 @@ impl PartialOrd for V8Platform {
 
 impl Ord for V8Platform {
     fn cmp(&self, other: &Self) -> Ordering {
-        (self.generation, self.version, self.build, &self.arch)
-            .cmp(&(other.generation, other.version, other.build, &other.arch))
+        (&self.version, &self.path).cmp(&(&other.version, &other.path))
     }
 }
 
-impl V8Platform {
-    pub fn get_app_by_type(&self, app_type: V8AppType) -> Option<PathBuf> {
-        let current_os = env::consts::OS;
-        let thick_client_path = match current_os {
-            "windows" => self.path.join("bin").join(app_type.value().to_owned() + ".exe"),
-            "linux" => self.path.join(app_type.value()),
-            "macos" => self.path.join(app_type.value()),
-            _ => PathBuf::new(),
-        };
+impl Hash for V8Platform {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.version.hash(state);
+        self.path.hash(state);
+    }
+}
 
-        if thick_client_path.exists() {
-            Some(thick_client_path)
-        } else {
-            None
-        }
+// Архитектура выводится, только если уже определена, чтобы отладочный вывод не обращался к диску
+impl fmt::Debug for V8Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("V8Platform")
+            .field("version", &self.version)
+            .field("arch", &self.arch.get())
+            .field("product_version", &self.product_version.get())
+            .field("components", &self.components.get())
+            .field("edition", &self.edition.get())
+            .field("path", &self.path)
+            .field("root", &self.root)
+            .field("source", &self.source)
+            .finish()
     }
+}
 
-    fn from_version_path(path: PathBuf) -> Option<Self> {
-        let str_path = path.to_str().unwrap_or("");
-        let v8_version_group_count = 4; // 8 3 13 1234
-
-        let v8_version = &str_path.split(r"\").last().unwrap_or("");
-        if PLATFORM_VERSION_REGEX.is_match(v8_version) {
-            let split_version: Vec<&str> = v8_version.split(".").collect();
-            if split_version.len() == v8_version_group_count {
-                let v8_platform = V8Platform {
-                    generation: split_version[1].parse().unwrap(),
-                    version: split_version[2].parse().unwrap(),
-                    build: split_version[3].parse().unwrap(),
-                    arch: V8Arch::from_path(&path),
-                    path,
-                };
-                return Some(v8_platform);
-            }
+impl V8Platform {
+    /// Создает описание платформы без обращения к диску. Может использоваться для передачи готового
+    /// списка платформ в [V8FinderBuilder::platforms](crate::v8_finder::V8FinderBuilder::platforms).
+    /// Способ определения разрядности - [V8ArchSource::Explicit], источник - [DiscoverySource::Explicit],
+    /// корневой каталог - родительский каталог `path`. Состав компонентов определяется по реальной файловой
+    /// системе; если по пути `path` ничего не установлено, вариант поставки считается [V8Edition::Full],
+    /// чтобы платформа выбиралась [V8Finder::get_platform](crate::v8_finder::V8Finder::get_platform).
+    /// Точный состав и вариант поставки можно задать через [V8Platform::with_components]
+    /// и [V8Platform::with_edition]
+    pub fn new(version: V8Version, arch: V8Arch, path: PathBuf) -> Self {
+        let root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        V8Platform {
+            version,
+            path,
+            root,
+            source: DiscoverySource::Explicit,
+            arch: OnceLock::from((arch, V8ArchSource::Explicit)),
+            product_version: OnceLock::new(),
+            components: OnceLock::new(),
+            edition: OnceLock::new(),
+            env: None,
+            packages: Arc::default(),
         }
-        None
     }
 
-    pub fn from_v8_dir(v8_dir: &V8Dir) -> Option<Vec<V8Platform>> {
-        let mut v8_platforms = Vec::new();
-        let sub_dirs: Vec<_> = v8_dir.path.read_dir().ok()?.collect();
-        for dir in sub_dirs {
-            if let Ok(dir) = dir {
-                let dir_path = dir.path();
-                let v8_platform = V8Platform::from_version_path(dir_path);
-                if let Some(v8_platform) = v8_platform {
-                    v8_platforms.push(v8_platform)
-                }
-            }
-        }
+    /// Задает корневой каталог установки, см. [V8Platform::root]
+    pub fn with_root<P: Into<PathBuf>>(mut self, root: P) -> Self {
+        self.root = root.into();
+        self
+    }
 
-        Some(v8_platforms)
+    /// Задает источник, из которого получена платформа, см. [V8Platform::source]
+    pub fn with_source(mut self, source: DiscoverySource) -> Self {
+        self.source = source;
+        self
     }
 
-    pub fn v8_platforms() -> Result<Vec<V8Platform>, io::Error> {
-        let v8_root_dirs = possible_v8installation_paths()?;
-        let v8_root_dirs: Vec<_> = v8_root_dirs.iter()
-            .filter(|v8_dir| v8_dir.path.exists())
-            .collect();
+    /// Задает состав компонентов явно, без обращения к диску
+    pub fn with_components(mut self, components: V8Components) -> Self {
+        self.components = OnceLock::from(components);
+        self
+    }
 
-        let mut all_v8_platforms = Vec::new();
-        for v8_root_dir in v8_root_dirs {
-            let platforms = V8Platform::from_v8_dir(v8_root_dir);
-            if let Some(mut platforms) = platforms {
-                all_v8_platforms.append(&mut platforms);
+    /// Версия платформы из имени каталога, например 8.3.13.1234. Версия из метаданных установленных
+    /// файлов доступна через [V8Platform::product_version]
+    pub fn version(&self) -> V8Version {
+        self.version
+    }
+
+    /// Каталог версии платформы, например `C:\Program Files\1cv8\8.3.13.1234`
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Корневой каталог установки, в котором найдена платформа, например `C:\Program Files\1cv8`
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Источник, из которого был получен корневой каталог платформы
+    pub fn source(&self) -> &DiscoverySource {
+        &self.source
+    }
+
+    /// Разрядность платформы. При первом обращении читается заголовок исполняемого файла,
+    /// см. [V8Arch::from_path]
+    pub fn arch(&self) -> V8Arch {
+        self.detected_arch().0
+    }
+
+    /// Способ, которым была определена разрядность
+    pub fn arch_source(&self) -> V8ArchSource {
+        self.detected_arch().1
+    }
+
+    /// Версия, прочитанная из метаданных установленных файлов: ресурса VS_VERSIONINFO исполняемых файлов
+    /// в Windows, базы dpkg в Linux, Info.plist в macOS. Читается при первом обращении, база dpkg
+    /// читается один раз для всех платформ одного поиска. `None`, если метаданные отсутствуют, их не удалось
+    /// прочитать, или платформа создана через [V8Platform::new]. В Linux поддерживается только dpkg: каталог
+    /// ищется в списках файлов пакетов, а скопированный или переименованный каталог - по контрольным суммам
+    /// его исполняемых файлов. Для платформ, установленных из rpm-пакетов, версия не определяется
+    pub fn product_version(&self) -> Option<V8Version> {
+        *self.product_version.get_or_init(|| {
+            let product_version = v8_metadata::read_product_version(self.env.as_deref()?, &self.packages, &self.path);
+            if let Some(product_version) = product_version.filter(|product_version| *product_version != self.version) {
+                warn!("Platform {} has version {} in its metadata", self.path.display(), product_version);
             }
-        }
-        Ok(all_v8_platforms)
+            product_version
+        })
     }
-}
 
-fn possible_v8installation_paths() -> Result<Vec<V8Dir>, io::Error> {
-    let current_os = env::consts::OS;
-    match current_os {
-        "windows" => v8_windows_paths(),
-        "linux" => v8_linux_paths(),
-        "macos" => v8_macos_paths(),
-        _ => Ok(Vec::new())
+    // Версия из метаданных, если она уже прочитана. Внешний `None` - еще не читалась
+    pub(crate) fn known_product_version(&self) -> Option<Option<V8Version>> {
+        self.product_version.get().copied()
     }
-}
 
-fn v8_linux_paths() -> Result<Vec<V8Dir>, io::Error> {
-    let starter_cfg_path = PathBuf::from("~/.1C/1cestart");
-    let mut locations_from_starter = read_locations_from_starter(starter_cfg_path)?;
-    let mut default_v8_paths = read_default_linux_paths()?;
+    /// Возвращает `true`, если версия из метаданных ([V8Platform::product_version]) прочитана и отличается
+    /// от версии из имени каталога ([V8Platform::version]), например, если каталог был переименован
+    /// или скопирован
+    pub fn has_version_mismatch(&self) -> bool {
+        self.product_version().is_some_and(|product_version| product_version != self.version)
+    }
 
-    let mut v8_all_paths = Vec::new();
-    v8_all_paths.append(&mut locations_from_starter);
-    v8_all_paths.append(&mut default_v8_paths);
+    /// Состав установленных компонентов платформы: клиенты, сервер, утилиты, модули веб-сервера.
+    /// Определяется при первом обращении по тем же путям, что и [V8Platform::get_app_by_type], каждый каталог
+    /// читается один раз. Для платформ, созданных через [V8Platform::new], проверяется реальная файловая система
+    ///```no_run
+    /// use v8find4rs::v8_app::V8Component;
+    /// use v8find4rs::v8_finder::{SearchPriority, V8Finder};
+    ///
+    /// let finder = V8Finder::new();
+    /// if let Some(platform) = finder.get_platform("8.3.22", SearchPriority::X64) {
+    ///     let components = platform.components();
+    ///     if components.is_server_only() {
+    ///         println!("{} is server-only", platform.version());
+    ///     }
+    ///     assert_eq!(components.contains(V8Component::IBCMD), platform.has_component(V8Component::IBCMD));
+    /// }
+    /// ```
+    pub fn components(&self) -> &V8Components {
+        self.components.get_or_init(|| V8Component::detect(self.env(), &self.path))
+    }
 
-    Ok(v8_all_paths)
-}
+    /// Вариант поставки платформы, см. [V8Edition::detect]. Определяется при первом обращении. Для платформ,
+    /// заданных явно ([DiscoverySource::Explicit]), без найденных компонентов - [V8Edition::Full]
+    pub fn edition(&self) -> V8Edition {
+        *self.edition.get_or_init(|| match V8Edition::detect(self.components(), &self.path) {
+            V8Edition::Unknown if self.source == DiscoverySource::Explicit => V8Edition::Full,
+            edition => edition,
+        })
+    }
 
-fn v8_macos_paths() -> Result<Vec<V8Dir>, io::Error> {
-    let starter_cfg_path = PathBuf::from("~/.1C/1cestart");
-    let mut locations_from_starter = read_locations_from_starter(starter_cfg_path)?;
-    let mut default_v8_paths = read_default_macos_paths();
+    /// Задает вариант поставки явно, без обращения к диску. Используется вместе с [V8Platform::new]
+    pub fn with_edition(mut self, edition: V8Edition) -> Self {
+        self.edition = OnceLock::from(edition);
+        self
+    }
 
-    let mut v8_all_paths = Vec::new();
-    v8_all_paths.append(&mut locations_from_starter);
-    v8_all_paths.append(&mut default_v8_paths);
+    /// Установлен ли компонент, см. [V8Platform::components]
+    pub fn has_component(&self, component: V8Component) -> bool {
+        self.components().contains(component)
+    }
 
-    Ok(v8_all_paths)
-}
+    fn detected_arch(&self) -> (V8Arch, V8ArchSource) {
+        *self.arch.get_or_init(|| match &self.env {
+            Some(env) => V8Arch::detect(env.as_ref(), &self.path),
+            None => (V8Arch::Unknown, V8ArchSource::Default),
+        })
+    }
 
-fn v8_windows_paths() -> Result<Vec<V8Dir>, io::Error> {
-    let all_users_starter = get_starter_path_windows("ALLUSERSPROFILE")?;
-    let mut v8_paths_all_users = read_locations_from_starter(
-        all_users_starter)?;
+    /// Возвращает путь к исполняемому файлу приложения указанного вида, если он существует.
+    /// Расположение файла определяется [V8AppLayout] по умолчанию
+    pub fn get_app_by_type(&self, app_type: V8AppType) -> Option<PathBuf> {
+        self.get_app(&app_type, &V8AppLayout::default())
+    }
 
-    let local_user_starter = get_starter_path_windows("APPDATA")?;
-    let mut v8_paths_local_user = read_locations_from_starter(
-        local_user_starter)?;
+    /// Аналог [V8Platform::get_app_by_type] с собственным описанием расположения файлов приложений
+    pub fn get_app(&self, app_type: &V8AppType, layout: &V8AppLayout) -> Option<PathBuf> {
+        let env = self.env();
+        layout.candidates(app_type, env.os(), &self.path).into_iter()
+            .find(|app_path| env.exists(app_path))
+    }
 
-    let mut default_v8_paths = read_default_windows_paths()?;
+    // Платформы, созданные через V8Platform::new, проверяются в реальной файловой системе
+    fn env(&self) -> &dyn Environment {
+        self.env.as_deref().unwrap_or(&SystemEnvironment)
+    }
 
-    let mut v8_all_paths = Vec::new();
-    v8_all_paths.append(&mut v8_paths_all_users);
-    v8_all_paths.append(&mut v8_paths_local_user);
-    v8_all_paths.append(&mut default_v8_paths);
+    fn from_version_path(env: &Arc<dyn Environment>, packages: &Arc<PackageIndex>, v8_dir: &V8Dir,
+                         path: PathBuf) -> Result<Option<Self>, V8FindError> {
+        let dir_name = last_path_component(&path);
+        let version = match parse_version_dir_name(dir_name)? {
+            Some(version) => version,
+            None => return Ok(None),
+        };
 
-    v8_all_paths = v8_all_paths.into_iter().unique().collect();
+        Ok(Some(V8Platform {
+            version,
+            path,
+            root: v8_dir.path.clone(),
+            source: v8_dir.source.clone(),
+            arch: OnceLock::new(),
+            product_version: OnceLock::new(),
+            components: OnceLock::new(),
+            edition: OnceLock::new(),
+            env: Some(Arc::clone(env)),
+            packages: Arc::clone(packages),
+        }))
+    }
 
-    Ok(v8_all_paths)
-}
+    pub(crate) fn from_v8_dir(env: &Arc<dyn Environment>, packages: &Arc<PackageIndex>, v8_dir: &V8Dir,
+                              issues: &mut Vec<DiscoveryIssue>) -> Result<Vec<V8Platform>, V8FindError> {
+        let mut v8_platforms = Vec::new();
+        let sub_dirs = env.read_dir(&v8_dir.path)
+            .map_err(|source| V8FindError::Io { path: v8_dir.path.clone(), source })?;
+        for dir_path in sub_dirs {
+            match V8Platform::from_version_path(env, packages, v8_dir, dir_path.clone()) {
+                Ok(Some(v8_platform)) => v8_platforms.push(v8_platform),
+                Ok(None) => {}
+                Err(err) => issues.push(DiscoveryIssue::new(v8_dir.source.clone(), Some(dir_path), err)),
+            }
+        }
 
-fn read_default_linux_paths() -> Result<Vec<V8Dir>, io::Error> {
-    let x32_old_path = PathBuf::from("/opt/1cv8/i386");
-    let x64_old_path = PathBuf::from("/opt/1cv8/x86_64");
-    let x32_new_path = PathBuf::from("/opt/v8.3/i386");
-    let x64_new_path = PathBuf::from("/opt/v8.3/x86_64");
+        Ok(v8_platforms)
+    }
 
-    let mut v8_paths: Vec<V8Dir> = Vec::new();
-    v8_paths.push(V8Dir::from_path(PathBuf::from(x32_old_path)));
-    v8_paths.push(V8Dir::from_path(PathBuf::from(x64_old_path)));
-    v8_paths.push(V8Dir::from_path(PathBuf::from(x32_new_path)));
-    v8_paths.push(V8Dir::from_path(PathBuf::from(x64_new_path)));
+    /// Опрашивает источники. Возвращает все корневые каталоги, полученные из источников, включая
+    /// несуществующие, и готовые описания платформ
+    pub(crate) fn query_sources(env: &Arc<dyn Environment>, sources: &[Box<dyn PlatformSource>],
+                                excluded_roots: &[PathBuf],
+                                issues: &mut Vec<DiscoveryIssue>) -> (Vec<V8Dir>, Vec<V8Platform>) {
+        let mut v8_root_dirs = Vec::new();
+        let mut v8_platforms = Vec::new();
+        for source in sources {
+            for candidate in source.discover(env.as_ref(), issues) {
+                match candidate {
+                    Candidate::Root(path) => v8_root_dirs.push(V8Dir::from_path(path, source.source())),
+                    Candidate::Platform(platform) => v8_platforms.push(platform),
+                }
+            }
+        }
 
-    Ok(v8_paths)
-}
+        let v8_root_dirs = v8_root_dirs.into_iter()
+            .unique()
+            .filter(|v8_dir| !excluded_roots.contains(&v8_dir.path))
+            .collect();
+        (v8_root_dirs, v8_platforms)
+    }
 
-fn read_default_macos_paths() -> Vec<V8Dir> {
-    vec![V8Dir::from_path(PathBuf::from("/opt/1cv8"))]
-}
+    /// Читает существующие корневые каталоги и возвращает найденные в них платформы
+    pub(crate) fn v8_platforms(env: &Arc<dyn Environment>, v8_root_dirs: &[V8Dir],
+                               issues: &mut Vec<DiscoveryIssue>) -> Vec<V8Platform> {
+        let existing_root_dirs: Vec<_> = v8_root_dirs.iter()
+            .filter(|v8_dir| env.exists(&v8_dir.path))
+            .cloned()
+            .collect();
 
-fn read_default_windows_paths() -> Result<Vec<V8Dir>, io::Error> {
-    let program_files_x86_var = env::var_os("PROGRAMFILES(x86)");
-    let program_files_var = env::var_os("PROGRAMFILES");
-    let local_appdata_var = env::var_os("LOCALAPPDATA");
+        let packages = Arc::default();
+        let mut all_v8_platforms = Vec::new();
+        for (v8_root_dir, result, mut root_issues) in V8Platform::scan_roots(env, &packages, &existing_root_dirs) {
+            issues.append(&mut root_issues);
+            match result {
+                Ok(mut platforms) => all_v8_platforms.append(&mut platforms),
+                Err(err) => issues.push(DiscoveryIssue::new(
+                    v8_root_dir.source.clone(), Some(v8_root_dir.path.clone()), err)),
+            }
+        }
+        all_v8_platforms
+    }
 
-    let mut v8_paths = Vec::with_capacity(7);
+    // Каждый корневой каталог читается в отдельном потоке. Результаты возвращаются в порядке
+    // каталогов, так что порядок платформ и проблем не зависит от того, какой поток завершился первым
+    fn scan_roots<'a>(env: &Arc<dyn Environment>, packages: &Arc<PackageIndex>,
+                      v8_root_dirs: &'a [V8Dir]) -> Vec<RootScan<'a>> {
+        let scan_root = |v8_root_dir: &'a V8Dir| {
+            let mut root_issues = Vec::new();
+            let result = V8Platform::from_v8_dir(env, packages, v8_root_dir, &mut root_issues);
+            (v8_root_dir, result, root_issues)
+        };
 
-    if let Some(program_files_x86_path) = program_files_x86_var {
-        v8_paths.push(V8Dir::from_path(
-            PathBuf::from(&program_files_x86_path).join("1cv8")));
+        if v8_root_dirs.len() < 2 {
+            return v8_root_dirs.iter().map(scan_root).collect();
+        }
 
-        v8_paths.push(V8Dir::from_path(
-            PathBuf::from(&program_files_x86_path).join("1cv82")));
+        thread::scope(|scope| {
+            let handles: Vec<_> = v8_root_dirs.iter()
+                .map(|v8_root_dir| scope.spawn(move || scan_root(v8_root_dir)))
+                .collect();
+            handles.into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|panic| panic::resume_unwind(panic)))
+                .collect()
+        })
     }
+}
 
-    if let Some(program_files_path) = program_files_var {
-        v8_paths.push(V8Dir::from_path(
-            PathBuf::from(&program_files_path).join("1cv8")));
-        v8_paths.push(V8Dir::from_path(
-            PathBuf::from(&program_files_path).join("1cv82")));
+// При сериализации определяются все сведения, которые иначе определялись бы лениво, кроме версии
+// из метаданных: для нее читаются файлы целиком, поэтому она сохраняется, только если уже прочитана.
+// Восстановленная платформа к диску не обращается
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct V8PlatformRepr {
+    version: V8Version,
+    arch: V8Arch,
+    arch_source: V8ArchSource,
+    path: PathBuf,
+    root: PathBuf,
+    source: DiscoverySource,
+    edition: V8Edition,
+    components: V8Components,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    product_version: Option<V8Version>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for V8Platform {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        V8PlatformRepr {
+            version: self.version,
+            arch: self.arch(),
+            arch_source: self.arch_source(),
+            path: self.path.clone(),
+            root: self.root.clone(),
+            source: self.source.clone(),
+            edition: self.edition(),
+            components: self.components().clone(),
+            product_version: self.product_version.get().copied().flatten(),
+        }.serialize(serializer)
     }
+}
 
-    if let Some(local_appdata_path) = local_appdata_var {
-        v8_paths.push(V8Dir::from_path(
-            PathBuf::from(&local_appdata_path).join("Programs").join("1cv8")));
-        v8_paths.push(V8Dir::from_path(
-            PathBuf::from(&local_appdata_path).join("Programs").join("1cv8_x86")));
-        v8_paths.push(V8Dir::from_path(
-            PathBuf::from(&local_appdata_path).join("Programs").join("1cv8_x64")));
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for V8Platform {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = V8PlatformRepr::deserialize(deserializer)?;
+        Ok(V8Platform {
+            version: repr.version,
+            path: repr.path,
+            root: repr.root,
+            source: repr.source,
+            arch: OnceLock::from((repr.arch, repr.arch_source)),
+            product_version: OnceLock::from(repr.product_version),
+            components: OnceLock::from(repr.components),
+            edition: OnceLock::from(repr.edition),
+            env: None,
+            packages: Arc::default(),
+        })
     }
+}
 
-    Ok(v8_paths)
+/// Платформа в кэше результатов поиска. В отличие от обычной сериализации сохраняются только уже
+/// определенные сведения, а версия из метаданных не сохраняется вовсе: остальное после загрузки
+/// определяется лениво, как у только что найденной платформы
+#[cfg(feature = "cache")]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(crate) struct CachedPlatform {
+    version: V8Version,
+    path: PathBuf,
+    root: PathBuf,
+    source: DiscoverySource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    arch: Option<V8Arch>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    arch_source: Option<V8ArchSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    edition: Option<V8Edition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    components: Option<V8Components>,
 }
 
-fn get_starter_path_windows(env_var_name: &str) -> Result<PathBuf, io::Error> {
-    let starter_file_suffix = r"1C\1CEStart\1CEStart.cfg"; // Windows Vista and higher
-    let config_root_dir = env::var_os(env_var_name);
+#[cfg(feature = "cache")]
+impl CachedPlatform {
+    pub(crate) fn new(platform: &V8Platform) -> Self {
+        CachedPlatform {
+            version: platform.version,
+            path: platform.path.clone(),
+            root: platform.root.clone(),
+            source: platform.source.clone(),
+            arch: platform.arch.get().map(|(arch, _)| *arch),
+            arch_source: platform.arch.get().map(|(_, arch_source)| *arch_source),
+            edition: platform.edition.get().copied(),
+            components: platform.components.get().cloned(),
+        }
+    }
 
-    if let Some(config_dir) = config_root_dir {
-        Ok(PathBuf::from(config_dir).join(starter_file_suffix))
-    } else {
-        Err(Error::new(ErrorKind::InvalidData, "Can't read sys variable"))
+    pub(crate) fn into_platform(self, env: &Arc<dyn Environment>, packages: &Arc<PackageIndex>) -> V8Platform {
+        let arch = match (self.arch, self.arch_source) {
+            (Some(arch), Some(arch_source)) => OnceLock::from((arch, arch_source)),
+            _ => OnceLock::new(),
+        };
+        V8Platform {
+            version: self.version,
+            path: self.path,
+            root: self.root,
+            source: self.source,
+            arch,
+            product_version: OnceLock::new(),
+            components: self.components.map(OnceLock::from).unwrap_or_default(),
+            edition: self.edition.map(OnceLock::from).unwrap_or_default(),
+            env: Some(Arc::clone(env)),
+            packages: Arc::clone(packages),
+        }
     }
 }
 
-fn read_locations_from_starter(starter_cfg_path: PathBuf) -> Result<Vec<V8Dir>, io::Error> {
-    let starter_file = File::open(starter_cfg_path)?;
+// Пути Windows могут разбираться и на других ОС, например, в MemoryEnvironment,
+// поэтому разделителями считаются и `\`, и `/`
+pub(crate) fn last_path_component(path: &Path) -> &str {
+    let str_path = path.to_str().unwrap_or("");
+    str_path.trim_end_matches(['\\', '/']).rsplit(['\\', '/']).next().unwrap_or("")
+}
+
+/// Разбирает имя каталога версии платформы. Имена, не начинающиеся с цифры (`common`, `conf` и т.п.),
+/// каталогами версий не считаются: для них возвращается `Ok(None)`. Имена, начинающиеся с цифры,
+/// но не соответствующие формату `8.3.22.1709[-суффикс]`, считаются ошибкой
+pub(crate) fn parse_version_dir_name(dir_name: &str) -> Result<Option<V8Version>, ParseVersionError> {
+    if !dir_name.starts_with(|c: char| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+
+    PLATFORM_VERSION_REGEX.captures(dir_name)
+        .and_then(|captures| captures[1].parse().ok())
+        .map(Some)
+        .ok_or_else(|| ParseVersionError::new(dir_name))
+}
 
-    let mut decoder = DecodeReaderBytes::new(starter_file);
-    let mut file_content = String::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let _ = decoder.read_to_string(&mut file_content);
+    fn dir_version(dir_name: &str) -> Option<String> {
+        parse_version_dir_name(dir_name).unwrap().map(|version| version.to_string())
+    }
 
-    let installed_locations: Vec<PathBuf> = file_content.lines()
-        .filter(|line| line.starts_with("InstalledLocation"))
-        .map(|location| &location[location.find("=").unwrap_or(0)..])
-        .map(|location| &location[1..])
-        .map(|location| PathBuf::from(location))
-        .collect();
+    #[test]
+    fn parses_version_dir_names() {
+        assert_eq!(dir_version("8.3.22.1709").as_deref(), Some("8.3.22.1709"));
+        assert_eq!(dir_version("8.3.22.1709-x64").as_deref(), Some("8.3.22.1709"));
+        assert_eq!(dir_version("8.3.22.1709_debug").as_deref(), Some("8.3.22.1709"));
+        assert_eq!(dir_version("common"), None);
+        assert_eq!(dir_version("conf"), None);
+        assert_eq!(dir_version(".8.3.22.1709"), None);
+    }
 
-    let mut v8_dirs = Vec::new();
-    for location in installed_locations {
-        let v8_dir = V8Dir::from_path(location);
-        v8_dirs.push(v8_dir)
-    };
+    #[test]
+    fn rejects_malformed_version_dir_names() {
+        for dir_name in ["8.3.22", "8.3.22.1709.1", "8.3.22.1709 old", "8.3.22.1709-", "8.3.22.1709.bak",
+                         "8.3.22.x", "1c8.3.22.1709"] {
+            assert!(parse_version_dir_name(dir_name).is_err(), "{}", dir_name);
+        }
+    }
 
-    Ok(v8_dirs)
+    #[test]
+    fn last_component_of_windows_and_unix_paths() {
+        assert_eq!(last_path_component(Path::new(r"C:\Program Files\1cv8\8.3.22.1709")), "8.3.22.1709");
+        assert_eq!(last_path_component(Path::new(r"C:\Program Files\1cv8\8.3.22.1709\")), "8.3.22.1709");
+        assert_eq!(last_path_component(Path::new("/opt/1cv8/x86_64/8.3.22.1709/")), "8.3.22.1709");
+        // Имя родительского каталога не должно попадать в разбор
+        assert_eq!(last_path_component(Path::new("/opt/8.3.22.1709/common")), "common");
+    }
 }
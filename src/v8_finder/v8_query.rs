@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+
+use crate::v8_app::{V8Arch, V8Component, V8Edition};
+use crate::v8_finder::v8_discovery::DiscoverySource;
+use crate::v8_finder::v8_platform::V8Platform;
+use crate::v8_version::V8VersionReq;
+
+/// Условия отбора платформ для [V8Finder::find_platforms](crate::v8_finder::V8Finder::find_platforms). Платформа подходит, если удовлетворяет всем
+/// заданным условиям. Несколько архитектур, вариантов поставки, корневых каталогов или источников
+/// означают, что подходит любой из них, а несколько компонентов - что требуются все.
+/// Незаданное условие не ограничивает отбор, в том числе учебная версия по умолчанию не исключается.
+/// Пример использования:
+///```no_run
+/// use v8find4rs::v8_app::{V8Arch, V8Component, V8Edition};
+/// use v8find4rs::v8_finder::{V8Finder, V8Query};
+///
+/// let finder = V8Finder::new();
+/// let query = V8Query::new()
+///     .version(">=8.3.20".parse().unwrap())
+///     .arch(V8Arch::X64)
+///     .edition(V8Edition::Full)
+///     .component(V8Component::IBCMD);
+/// for platform in finder.find_platforms(&query) {
+///     println!("{} {}", platform.version(), platform.path().display());
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct V8Query {
+    version_req: Option<V8VersionReq>,
+    archs: Vec<V8Arch>,
+    editions: Vec<V8Edition>,
+    components: Vec<V8Component>,
+    roots: Vec<PathBuf>,
+    sources: Vec<DiscoverySource>,
+}
+
+impl V8Query {
+    /// Создание запроса без условий: ему соответствуют все найденные платформы
+    pub fn new() -> Self {
+        V8Query::default()
+    }
+
+    /// Требование к версии платформы. Повторный вызов заменяет ранее заданное требование
+    pub fn version(mut self, version_req: V8VersionReq) -> Self {
+        self.version_req = Some(version_req);
+        self
+    }
+
+    /// Добавляет допустимую архитектуру
+    pub fn arch(mut self, arch: V8Arch) -> Self {
+        self.archs.push(arch);
+        self
+    }
+
+    /// Добавляет допустимый вариант поставки
+    pub fn edition(mut self, edition: V8Edition) -> Self {
+        self.editions.push(edition);
+        self
+    }
+
+    /// Добавляет допустимые варианты поставки, см. [V8Query::edition]
+    pub fn editions(mut self, editions: &[V8Edition]) -> Self {
+        self.editions.extend_from_slice(editions);
+        self
+    }
+
+    /// Добавляет компонент, который должен быть установлен
+    pub fn component(mut self, component: V8Component) -> Self {
+        self.components.push(component);
+        self
+    }
+
+    /// Добавляет допустимый корневой каталог установки, см. [V8Platform::root]
+    pub fn root<P: Into<PathBuf>>(mut self, root: P) -> Self {
+        self.roots.push(root.into());
+        self
+    }
+
+    /// Добавляет допустимый источник, из которого была получена платформа, см. [V8Platform::source]
+    pub fn source(mut self, source: DiscoverySource) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    /// Проверяет, удовлетворяет ли платформа условиям запроса. Ленивые сведения о платформе определяются
+    /// только для заданных условий
+    pub fn matches(&self, platform: &V8Platform) -> bool {
+        self.version_req.as_ref().is_none_or(|version_req| version_req.matches(&platform.version()))
+            && (self.roots.is_empty() || self.roots.iter().any(|root| root == platform.root()))
+            && (self.sources.is_empty() || self.sources.contains(platform.source()))
+            && (self.archs.is_empty() || self.archs.contains(&platform.arch()))
+            && (self.editions.is_empty() || self.editions.contains(&platform.edition()))
+            && self.components.iter().all(|component| platform.has_component(*component))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::v8_app::v8_binary::tests::elf;
+    use crate::v8_env::MemoryEnvironment;
+    use crate::v8_finder::V8Finder;
+
+    // Порядок обнаружения: сначала каталог из 1CEStart.cfg, затем стандартные пути
+    fn finder() -> V8Finder {
+        let env = MemoryEnvironment::new("linux")
+            .with_home("/home/user")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/1cv8", elf(2, 1, 62))
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/ibcmd", "")
+            .with_file("/opt/1cv8/x86_64/8.3.24.1342/1cv8c", elf(2, 1, 62))
+            .with_file("/opt/1cv8/i386/8.3.23.1865/1cv8", elf(1, 1, 3))
+            .with_file("/opt/1cv8t/x86_64/8.3.25.1000/1cv8", elf(2, 1, 62))
+            .with_file("/etc/1C/1cestart/1cestart.cfg", "InstalledLocation=/srv/1c\n")
+            .with_file("/srv/1c/8.3.22.1709/1cv8", elf(2, 1, 62))
+            .with_file("/srv/1c/8.3.22.1709/ibcmd", "")
+            .with_file("/srv/1c/8.3.21.1895/ragent", elf(2, 1, 62))
+            .with_file("/srv/1c/8.3.21.1895/rmngr", "")
+            .with_file("/srv/1c/8.3.21.1895/rphost", "");
+        V8Finder::builder().environment(env).build()
+    }
+
+    fn paths(finder: &V8Finder, query: V8Query) -> Vec<&str> {
+        finder.find_platforms(&query)
+            .map(|platform| platform.path().to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn all_platforms_from_newest_to_oldest() {
+        let finder = finder();
+        assert_eq!(paths(&finder, V8Query::new()), [
+            "/opt/1cv8t/x86_64/8.3.25.1000",
+            "/opt/1cv8/x86_64/8.3.24.1342",
+            "/opt/1cv8/i386/8.3.23.1865",
+            "/srv/1c/8.3.22.1709",
+            "/opt/1cv8/x86_64/8.3.22.1709",
+            "/srv/1c/8.3.21.1895",
+        ]);
+        let oldest = finder.find_platforms(&V8Query::new()).next_back().unwrap();
+        assert_eq!(oldest.path(), Path::new("/srv/1c/8.3.21.1895"));
+        assert_eq!(finder.find_platforms(&V8Query::new()).len(), 6);
+    }
+
+    #[test]
+    fn filters() {
+        let finder = finder();
+        let version = |version_req: &str| V8Query::new().version(version_req.parse().unwrap());
+
+        assert_eq!(paths(&finder, version(">=8.3.22, <8.3.24")),
+                   ["/opt/1cv8/i386/8.3.23.1865", "/srv/1c/8.3.22.1709", "/opt/1cv8/x86_64/8.3.22.1709"]);
+        assert_eq!(paths(&finder, V8Query::new().arch(V8Arch::X86).arch(V8Arch::Arm64)),
+                   ["/opt/1cv8/i386/8.3.23.1865"]);
+        assert_eq!(paths(&finder, V8Query::new().editions(&[V8Edition::Training, V8Edition::ServerOnly])),
+                   ["/opt/1cv8t/x86_64/8.3.25.1000", "/srv/1c/8.3.21.1895"]);
+        assert_eq!(paths(&finder, V8Query::new().component(V8Component::ThickClient).component(V8Component::IBCMD)),
+                   ["/srv/1c/8.3.22.1709", "/opt/1cv8/x86_64/8.3.22.1709"]);
+        assert_eq!(paths(&finder, V8Query::new().root("/srv/1c").edition(V8Edition::Full)),
+                   ["/srv/1c/8.3.22.1709"]);
+        assert_eq!(paths(&finder, V8Query::new().source(DiscoverySource::AllUsersStarter)),
+                   ["/srv/1c/8.3.22.1709", "/srv/1c/8.3.21.1895"]);
+    }
+
+    #[test]
+    fn no_matching_platforms() {
+        let finder = finder();
+        let query = V8Query::new().version("8.2".parse().unwrap());
+        assert_eq!(finder.find_platforms(&query).len(), 0);
+        let query = V8Query::new().arch(V8Arch::E2k).component(V8Component::ThinClient);
+        assert!(paths(&finder, query).is_empty());
+    }
+}
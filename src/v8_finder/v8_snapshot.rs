@@ -0,0 +1,335 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::v8_finder::v8_platform::V8Platform;
+use crate::v8_finder::V8Finder;
+
+/// Снимок найденных платформ на момент вызова [V8Finder::snapshot]. В отличие от [V8Finder] все сведения
+/// о платформах (архитектура, вариант поставки, компоненты) в снимке уже определены, так что снимки
+/// можно сравнивать между собой через [V8Snapshot::diff], в том числе снимки с разных компьютеров,
+/// сохраненные в JSON.
+///
+/// Снимки равны, если [V8Snapshot::diff] между ними пуст: в отличие от сравнения самих [V8Platform]
+/// учитываются все сведения о платформах, но не порядок их обнаружения
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct V8Snapshot {
+    platforms: Vec<V8Platform>,
+}
+
+/// Сведения о платформе, изменившиеся между двумя снимками
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum V8PlatformField {
+    /// Архитектура
+    Arch,
+    /// Каталог версии платформы
+    Path,
+    /// Корневой каталог установки
+    Root,
+    /// Вариант поставки
+    Edition,
+    /// Состав компонентов
+    Components,
+    /// Версия из метаданных установленных файлов. Сравнивается, только если она прочитана в обоих снимках,
+    /// см. [V8Finder::snapshot_with_versions]
+    ProductVersion,
+}
+
+/// Платформа, присутствующая в обоих снимках, но с различающимися сведениями
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct V8PlatformChange {
+    /// Платформа в исходном снимке
+    pub old: V8Platform,
+    /// Платформа в новом снимке
+    pub new: V8Platform,
+    /// Изменившиеся сведения
+    pub fields: Vec<V8PlatformField>,
+}
+
+/// Разница между двумя снимками, см. [V8Snapshot::diff]. При выводе через `Display` каждая платформа
+/// выводится отдельной строкой: `+` - добавленная, `-` - удаленная, `~` - измененная
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct V8SnapshotDiff {
+    /// Платформы, которых не было в исходном снимке
+    pub added: Vec<V8Platform>,
+    /// Платформы, которых нет в новом снимке
+    pub removed: Vec<V8Platform>,
+    /// Платформы, сведения о которых изменились
+    pub changed: Vec<V8PlatformChange>,
+}
+
+impl V8Snapshot {
+    pub(crate) fn capture(finder: &V8Finder, with_versions: bool) -> Self {
+        let platforms = finder.platforms.iter()
+            .map(|platform| {
+                // Определяем ленивые сведения до клонирования, чтобы они попали в снимок
+                platform.arch();
+                platform.edition();
+                if with_versions {
+                    platform.product_version();
+                }
+                platform.clone()
+            })
+            .collect();
+
+        V8Snapshot { platforms }
+    }
+
+    /// Платформы снимка в порядке обнаружения
+    pub fn platforms(&self) -> &[V8Platform] {
+        &self.platforms
+    }
+
+    /// Сравнивает снимок с более новым. Платформы сопоставляются сначала по каталогу версии, затем
+    /// по версии и архитектуре: так перенос платформы в другой каталог считается изменением, а не
+    /// удалением и добавлением
+    ///```no_run
+    /// use v8find4rs::v8_finder::V8Finder;
+    ///
+    /// let before = V8Finder::new().snapshot();
+    /// // ... установка или удаление платформ
+    /// let after = V8Finder::new().snapshot();
+    /// let diff = before.diff(&after);
+    /// if !diff.is_empty() {
+    ///     println!("{}", diff);
+    /// }
+    /// ```
+    pub fn diff(&self, newer: &V8Snapshot) -> V8SnapshotDiff {
+        let mut old_platforms: Vec<Option<&V8Platform>> = self.platforms.iter().map(Some).collect();
+        let mut new_platforms: Vec<Option<&V8Platform>> = newer.platforms.iter().map(Some).collect();
+        let mut diff = V8SnapshotDiff::default();
+
+        let same_path = |old: &V8Platform, new: &V8Platform| old.path() == new.path();
+        let same_build = |old: &V8Platform, new: &V8Platform| old.version() == new.version() && old.arch() == new.arch();
+        for matches in [&same_path as &dyn Fn(&V8Platform, &V8Platform) -> bool, &same_build] {
+            for old_slot in old_platforms.iter_mut() {
+                let old = match old_slot {
+                    Some(old) => *old,
+                    None => continue,
+                };
+                let new_slot = new_platforms.iter_mut()
+                    .find(|new_slot| new_slot.is_some_and(|new| matches(old, new)));
+                if let Some(new_slot) = new_slot {
+                    let new = new_slot.take().unwrap();
+                    old_slot.take();
+                    let fields = changed_fields(old, new);
+                    if !fields.is_empty() {
+                        diff.changed.push(V8PlatformChange { old: old.clone(), new: new.clone(), fields });
+                    }
+                }
+            }
+        }
+
+        diff.removed = old_platforms.into_iter().flatten().cloned().collect();
+        diff.added = new_platforms.into_iter().flatten().cloned().collect();
+        diff
+    }
+
+    /// Выгружает снимок в JSON
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Загружает снимок из JSON, полученного через [V8Snapshot::to_json]
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> serde_json::Result<V8Snapshot> {
+        serde_json::from_str(json)
+    }
+}
+
+impl PartialEq for V8Snapshot {
+    fn eq(&self, other: &Self) -> bool {
+        self.diff(other).is_empty()
+    }
+}
+
+impl Eq for V8Snapshot {}
+
+impl From<&V8Finder> for V8Snapshot {
+    fn from(finder: &V8Finder) -> Self {
+        V8Snapshot::capture(finder, false)
+    }
+}
+
+fn changed_fields(old: &V8Platform, new: &V8Platform) -> Vec<V8PlatformField> {
+    let mut fields = Vec::new();
+    if old.arch() != new.arch() {
+        fields.push(V8PlatformField::Arch);
+    }
+    if old.path() != new.path() {
+        fields.push(V8PlatformField::Path);
+    }
+    if old.root() != new.root() {
+        fields.push(V8PlatformField::Root);
+    }
+    if old.edition() != new.edition() {
+        fields.push(V8PlatformField::Edition);
+    }
+    if old.components() != new.components() {
+        fields.push(V8PlatformField::Components);
+    }
+    // Версия из метаданных, не прочитанная хотя бы в одном из снимков, не сравнивается и не читается
+    if let (Some(old_version), Some(new_version)) = (old.known_product_version(), new.known_product_version()) {
+        if old_version != new_version {
+            fields.push(V8PlatformField::ProductVersion);
+        }
+    }
+    fields
+}
+
+impl V8SnapshotDiff {
+    /// Снимки не различаются
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Выгружает разницу в JSON
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl fmt::Display for V8SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+
+        let mut lines = Vec::new();
+        for platform in &self.added {
+            lines.push(format!("+ {}", describe(platform)));
+        }
+        for platform in &self.removed {
+            lines.push(format!("- {}", describe(platform)));
+        }
+        for change in &self.changed {
+            let fields: Vec<_> = change.fields.iter()
+                .map(|field| describe_field(*field, &change.old, &change.new))
+                .collect();
+            lines.push(format!("~ {}: {}", describe(&change.old), fields.join("; ")));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+fn describe(platform: &V8Platform) -> String {
+    format!("{} {:?} {}", platform.version(), platform.arch(), platform.path().display())
+}
+
+fn describe_field(field: V8PlatformField, old: &V8Platform, new: &V8Platform) -> String {
+    let display_path = |path: &Path| path.display().to_string();
+    let (name, old_value, new_value) = match field {
+        V8PlatformField::Arch => ("arch", format!("{:?}", old.arch()), format!("{:?}", new.arch())),
+        V8PlatformField::Path => ("path", display_path(old.path()), display_path(new.path())),
+        V8PlatformField::Root => ("root", display_path(old.root()), display_path(new.root())),
+        V8PlatformField::Edition => ("edition", format!("{:?}", old.edition()), format!("{:?}", new.edition())),
+        V8PlatformField::Components => (
+            "components",
+            format!("{:?}", old.components().iter().collect::<Vec<_>>()),
+            format!("{:?}", new.components().iter().collect::<Vec<_>>()),
+        ),
+        V8PlatformField::ProductVersion => (
+            "product version",
+            format!("{:?}", old.known_product_version().flatten()),
+            format!("{:?}", new.known_product_version().flatten()),
+        ),
+    };
+    format!("{} {} -> {}", name, old_value, new_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::v8_app::{V8Arch, V8Component, V8Edition};
+
+    fn platform(version: &str, arch: V8Arch, path: &str) -> V8Platform {
+        V8Platform::new(version.parse().unwrap(), arch, PathBuf::from(path))
+            .with_components(iter::once(V8Component::ThickClient).collect())
+            .with_edition(V8Edition::Full)
+    }
+
+    fn snapshot(platforms: Vec<V8Platform>) -> V8Snapshot {
+        V8Snapshot { platforms }
+    }
+
+    #[test]
+    fn equal_snapshots() {
+        let a = platform("8.3.22.1709", V8Arch::X64, "/opt/1cv8/x86_64/8.3.22.1709");
+        let b = platform("8.3.23.1865", V8Arch::X64, "/opt/1cv8/x86_64/8.3.23.1865");
+        assert_eq!(snapshot(vec![a.clone(), b.clone()]), snapshot(vec![a.clone(), b.clone()]));
+        // Порядок обнаружения не важен
+        assert_eq!(snapshot(vec![a.clone(), b.clone()]), snapshot(vec![b, a]));
+    }
+
+    #[test]
+    fn snapshots_differ_in_details_not_compared_by_platform_eq() {
+        let old = platform("8.3.22.1709", V8Arch::X64, "/opt/1cv8/x86_64/8.3.22.1709");
+        let moved = platform("8.3.22.1709", V8Arch::X64, "/srv/1c/8.3.22.1709");
+        let server = platform("8.3.22.1709", V8Arch::X64, "/opt/1cv8/x86_64/8.3.22.1709")
+            .with_components(iter::once(V8Component::Server).collect())
+            .with_edition(V8Edition::ServerOnly);
+        // Платформы сравниваются по версии и каталогу, но не по составу компонентов
+        assert_ne!(old, moved);
+        assert_eq!(old, server);
+
+        let before = snapshot(vec![old.clone()]);
+        assert_ne!(before, snapshot(vec![moved.clone()]));
+        assert_ne!(before, snapshot(vec![server.clone()]));
+
+        let diff = before.diff(&snapshot(vec![moved]));
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].fields, [V8PlatformField::Path, V8PlatformField::Root]);
+
+        let diff = before.diff(&snapshot(vec![server]));
+        assert_eq!(diff.changed[0].fields, [V8PlatformField::Edition, V8PlatformField::Components]);
+    }
+
+    #[test]
+    fn product_versions_are_compared_only_when_read() {
+        use crate::v8_env::MemoryEnvironment;
+
+        let env = |version: &str| MemoryEnvironment::new("linux")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/1cv8", "")
+            .with_file("/var/lib/dpkg/status",
+                       format!("Package: 1c-enterprise83-client\nStatus: install ok installed\nVersion: {}\n", version))
+            .with_file("/var/lib/dpkg/info/1c-enterprise83-client.list", "/opt/1cv8/x86_64/8.3.22.1709\n");
+        let finder = |version: &str| V8Finder::builder().no_defaults().root("/opt/1cv8/x86_64")
+            .environment(env(version))
+            .build();
+        let before = finder("8.3.22-1709");
+        let after = finder("8.3.22-1710");
+
+        let plain = before.snapshot();
+        assert_eq!(plain.platforms()[0].known_product_version(), None);
+        assert_eq!(before.platforms()[0].known_product_version(), None);
+        let versioned = after.snapshot_with_versions();
+        assert_eq!(versioned.platforms()[0].known_product_version(), Some(Some("8.3.22.1710".parse().unwrap())));
+        assert!(plain.diff(&versioned).is_empty());
+
+        let diff = before.snapshot_with_versions().diff(&versioned);
+        assert_eq!(diff.changed[0].fields, [V8PlatformField::ProductVersion]);
+        assert!(diff.to_string().contains("product version"), "{}", diff);
+    }
+
+    #[test]
+    fn added_and_removed_platforms() {
+        let old = platform("8.3.22.1709", V8Arch::X64, "/opt/1cv8/x86_64/8.3.22.1709");
+        let new = platform("8.3.23.1865", V8Arch::X64, "/opt/1cv8/x86_64/8.3.23.1865");
+        let diff = snapshot(vec![old]).diff(&snapshot(vec![new]));
+
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.added.len(), 1);
+        assert!(diff.changed.is_empty());
+        assert_eq!(diff.to_string().lines().count(), 2);
+        assert!(snapshot(Vec::new()).diff(&snapshot(Vec::new())).is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::v8_env::Environment;
+use crate::v8_error::V8FindError;
+use crate::v8_finder::v8_discovery::{DiscoveryIssue, DiscoverySource};
+use crate::v8_finder::v8_platform::V8Platform;
+use crate::v8_starter::StarterConfig;
+
+/// Результат опроса источника: либо корневой каталог, подкаталоги которого будут проверены на наличие
+/// версий платформы, либо уже готовое описание платформы
+#[derive(Debug)]
+pub enum Candidate {
+    /// Корневой каталог установки, например `C:\Program Files\1cv8`
+    Root(PathBuf),
+    /// Готовое описание платформы
+    Platform(V8Platform),
+}
+
+/// Источник, из которого [V8Finder](crate::v8_finder::V8Finder) получает каталоги установки или описания
+/// платформ. Позволяет добавить собственные способы поиска, например, каталог на сетевом диске
+/// или файл инвентаризации.
+///```no_run
+/// use v8find4rs::v8_env::Environment;
+/// use v8find4rs::v8_finder::{Candidate, DiscoveryIssue, DiscoverySource, PlatformSource, V8Finder};
+///
+/// struct NasMirror;
+///
+/// impl PlatformSource for NasMirror {
+///     fn source(&self) -> DiscoverySource {
+///         DiscoverySource::Custom("nas mirror".to_owned())
+///     }
+///
+///     fn discover(&self, _env: &dyn Environment, _issues: &mut Vec<DiscoveryIssue>) -> Vec<Candidate> {
+///         vec![Candidate::Root("/mnt/nas/1c".into())]
+///     }
+/// }
+///
+/// let finder = V8Finder::builder().source(NasMirror).build();
+/// ```
+pub trait PlatformSource: Send + Sync {
+    /// Вид источника. Используется для диагностики: указывается в [DiscoveryIssue] при проблемах
+    /// с каталогами, полученными из этого источника
+    fn source(&self) -> DiscoverySource;
+
+    /// Опрашивает источник. Обращения к ОС и файловой системе следует выполнять через `env`.
+    /// Проблемы, не мешающие вернуть часть результата, записываются в `issues`
+    fn discover(&self, env: &dyn Environment, issues: &mut Vec<DiscoveryIssue>) -> Vec<Candidate>;
+
+    /// Файлы настроек, изменение которых может изменить результат [PlatformSource::discover]. Используются
+    /// для отслеживания изменений. По умолчанию таких файлов нет
+    fn config_files(&self, _env: &dyn Environment) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StarterScope {
+    AllUsers,
+    CurrentUser,
+}
+
+/// Источник, читающий каталоги установки из параметров InstalledLocation файла 1CEStart.cfg.
+/// Отсутствие файла ошибкой не считается
+#[derive(Debug, Clone)]
+pub struct StarterConfigSource {
+    scope: StarterScope,
+}
+
+impl StarterConfigSource {
+    /// 1CEStart.cfg для всех пользователей: `%ALLUSERSPROFILE%\1C\1CEStart\1CEStart.cfg` в Windows,
+    /// `/etc/1C/1cestart/1cestart.cfg` в Linux и macOS
+    pub fn all_users() -> Self {
+        StarterConfigSource { scope: StarterScope::AllUsers }
+    }
+
+    /// 1CEStart.cfg текущего пользователя: `%APPDATA%\1C\1CEStart\1CEStart.cfg` в Windows,
+    /// `~/.1C/1cestart/1cestart.cfg` в Linux и macOS
+    pub fn current_user() -> Self {
+        StarterConfigSource { scope: StarterScope::CurrentUser }
+    }
+
+    fn starter_cfg_path(&self, env: &dyn Environment) -> Result<Option<PathBuf>, V8FindError> {
+        match (env.os(), self.scope) {
+            ("windows", StarterScope::AllUsers) => get_starter_path_windows(env, "ALLUSERSPROFILE").map(Some),
+            ("windows", StarterScope::CurrentUser) => get_starter_path_windows(env, "APPDATA").map(Some),
+            ("linux", StarterScope::AllUsers) | ("macos", StarterScope::AllUsers) => {
+                Ok(Some(PathBuf::from("/etc/1C/1cestart/1cestart.cfg")))
+            }
+            ("linux", StarterScope::CurrentUser) | ("macos", StarterScope::CurrentUser) => {
+                let home_dir = env.home_dir().ok_or_else(|| V8FindError::EnvVar { name: "HOME".to_owned() })?;
+                Ok(Some(home_dir.join(".1C").join("1cestart").join("1cestart.cfg")))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl PlatformSource for StarterConfigSource {
+    fn source(&self) -> DiscoverySource {
+        match self.scope {
+            StarterScope::AllUsers => DiscoverySource::AllUsersStarter,
+            StarterScope::CurrentUser => DiscoverySource::CurrentUserStarter,
+        }
+    }
+
+    fn discover(&self, env: &dyn Environment, issues: &mut Vec<DiscoveryIssue>) -> Vec<Candidate> {
+        let starter_cfg_path = match self.starter_cfg_path(env) {
+            Ok(Some(starter_cfg_path)) => starter_cfg_path,
+            Ok(None) => return Vec::new(),
+            Err(err) => {
+                issues.push(DiscoveryIssue::new(self.source(), None, err));
+                return Vec::new();
+            }
+        };
+
+        match read_locations_from_starter(env, &starter_cfg_path) {
+            Ok(locations) => locations.into_iter().map(Candidate::Root).collect(),
+            Err(err) => {
+                issues.push(DiscoveryIssue::new(self.source(), Some(starter_cfg_path), err));
+                Vec::new()
+            }
+        }
+    }
+
+    fn config_files(&self, env: &dyn Environment) -> Vec<PathBuf> {
+        self.starter_cfg_path(env).ok().flatten().into_iter().collect()
+    }
+}
+
+/// Источник, возвращающий стандартные пути установки платформы для текущей ОС
+#[derive(Debug, Clone, Default)]
+pub struct DefaultPathsSource;
+
+impl PlatformSource for DefaultPathsSource {
+    fn source(&self) -> DiscoverySource {
+        DiscoverySource::DefaultPaths
+    }
+
+    fn discover(&self, env: &dyn Environment, _issues: &mut Vec<DiscoveryIssue>) -> Vec<Candidate> {
+        let default_v8_paths = match env.os() {
+            "windows" => read_default_windows_paths(env),
+            "linux" => read_default_linux_paths(),
+            "macos" => read_default_macos_paths(),
+            _ => Vec::new()
+        };
+
+        default_v8_paths.into_iter().map(Candidate::Root).collect()
+    }
+}
+
+/// Источник, возвращающий заранее заданный список корневых каталогов
+#[derive(Debug, Clone, Default)]
+pub struct RootsSource {
+    roots: Vec<PathBuf>,
+}
+
+impl RootsSource {
+    /// Создание источника из списка каталогов
+    pub fn new<I, P>(roots: I) -> Self
+        where I: IntoIterator<Item=P>,
+              P: Into<PathBuf> {
+        RootsSource { roots: roots.into_iter().map(Into::into).collect() }
+    }
+}
+
+impl PlatformSource for RootsSource {
+    fn source(&self) -> DiscoverySource {
+        DiscoverySource::CustomRoots
+    }
+
+    fn discover(&self, _env: &dyn Environment, _issues: &mut Vec<DiscoveryIssue>) -> Vec<Candidate> {
+        self.roots.iter().cloned().map(Candidate::Root).collect()
+    }
+}
+
+fn read_default_linux_paths() -> Vec<PathBuf> {
+    let x32_old_path = PathBuf::from("/opt/1cv8/i386");
+    let x64_old_path = PathBuf::from("/opt/1cv8/x86_64");
+    let x32_new_path = PathBuf::from("/opt/v8.3/i386");
+    let x64_new_path = PathBuf::from("/opt/v8.3/x86_64");
+    let arm64_path = PathBuf::from("/opt/1cv8/aarch64");
+    let e2k_path = PathBuf::from("/opt/1cv8/e2k");
+    // Учебная версия
+    let x32_training_path = PathBuf::from("/opt/1cv8t/i386");
+    let x64_training_path = PathBuf::from("/opt/1cv8t/x86_64");
+
+    vec![x32_old_path, x64_old_path, x32_new_path, x64_new_path, arm64_path, e2k_path,
+         x32_training_path, x64_training_path]
+}
+
+fn read_default_macos_paths() -> Vec<PathBuf> {
+    vec![PathBuf::from("/opt/1cv8"), PathBuf::from("/opt/1cv8t")]
+}
+
+// 1cv8t - учебная версия, 1cv8c - отдельно установленный тонкий клиент
+fn read_default_windows_paths(env: &dyn Environment) -> Vec<PathBuf> {
+    let program_files_x86_var = env.var_os("PROGRAMFILES(x86)");
+    let program_files_var = env.var_os("PROGRAMFILES");
+    let local_appdata_var = env.var_os("LOCALAPPDATA");
+
+    let mut v8_paths = Vec::with_capacity(13);
+
+    for program_files_path in program_files_x86_var.iter().chain(program_files_var.iter()) {
+        for root in ["1cv8", "1cv82", "1cv8t", "1cv8c"] {
+            v8_paths.push(PathBuf::from(program_files_path).join(root));
+        }
+    }
+
+    if let Some(local_appdata_path) = local_appdata_var {
+        for root in ["1cv8", "1cv8_x86", "1cv8_x64", "1cv8t", "1cv8c"] {
+            v8_paths.push(PathBuf::from(&local_appdata_path).join("Programs").join(root));
+        }
+    }
+
+    v8_paths
+}
+
+fn get_starter_path_windows(env: &dyn Environment, env_var_name: &str) -> Result<PathBuf, V8FindError> {
+    let config_root_dir = env.var_os(env_var_name);
+
+    if let Some(config_dir) = config_root_dir {
+        // Windows Vista and higher
+        Ok(PathBuf::from(config_dir).join("1C").join("1CEStart").join("1CEStart.cfg"))
+    } else {
+        Err(V8FindError::EnvVar { name: env_var_name.to_owned() })
+    }
+}
+
+// Отсутствующий файл настроек не является ошибкой: стартер может быть просто не установлен
+fn read_locations_from_starter(env: &dyn Environment, starter_cfg_path: &Path) -> Result<Vec<PathBuf>, V8FindError> {
+    let starter_file = match env.read(starter_cfg_path) {
+        Ok(starter_file) => starter_file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(V8FindError::StarterConfig { path: starter_cfg_path.to_owned(), source: err }),
+    };
+
+    let starter_config = StarterConfig::parse(&starter_file);
+    let installed_locations = starter_config.installed_locations().into_iter()
+        .map(Path::to_path_buf)
+        .collect();
+
+    Ok(installed_locations)
+}
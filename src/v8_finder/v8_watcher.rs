@@ -0,0 +1,338 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, Weak};
+use std::thread;
+use std::time::Duration;
+
+use log::warn;
+use notify::event::{MetadataKind, ModifyKind};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::v8_error::V8FindError;
+use crate::v8_finder::v8_platform::V8Platform;
+use crate::v8_finder::v8_snapshot::{V8PlatformChange, V8Snapshot};
+use crate::v8_finder::V8Finder;
+
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Изменение, обнаруженное [V8Watcher]
+// События передаются по одному через канал, поэтому разница в размере вариантов не важна
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum V8WatchEvent {
+    /// Установлена новая платформа
+    PlatformAdded(V8Platform),
+    /// Платформа удалена
+    PlatformRemoved(V8Platform),
+    /// Изменились сведения о платформе, например, в каталог версии были скопированы исполняемые файлы
+    PlatformChanged(V8PlatformChange),
+    /// Изменился, появился или был удален файл 1CEStart.cfg (или другой файл настроек источника,
+    /// см. [PlatformSource::config_files](crate::v8_finder::PlatformSource::config_files))
+    StarterConfigChanged(PathBuf),
+}
+
+/// Отслеживает изменения в корневых каталогах установки, каталогах найденных платформ и файлах 1CEStart.cfg.
+/// После каждого изменения поиск повторяется с теми же настройками, общий [V8Finder] заменяется новым,
+/// а различия передаются в [V8Watcher::events]. Изменения, происходящие одно за другим (например,
+/// копирование файлов при установке), обрабатываются одним повторным поиском после паузы.
+/// Отслеживание прекращается при удалении объекта.
+/// Пример использования:
+///```no_run
+/// use std::sync::{Arc, RwLock};
+/// use v8find4rs::v8_finder::{SearchPriority, V8Finder, V8WatchEvent, V8Watcher};
+///
+/// let finder = Arc::new(RwLock::new(V8Finder::new()));
+/// let watcher = V8Watcher::new(Arc::clone(&finder)).unwrap();
+/// for event in watcher.events() {
+///     if let V8WatchEvent::PlatformAdded(platform) = event {
+///         println!("installed {}", platform.version());
+///         let latest = finder.read().unwrap().get_platform("8.3", SearchPriority::X64).cloned();
+///     }
+/// }
+/// ```
+pub struct V8Watcher {
+    finder: Arc<RwLock<V8Finder>>,
+    events: Receiver<V8WatchEvent>,
+    // Единственная сильная ссылка: при удалении V8Watcher закрывается канал событий notify,
+    // и поток обработки завершается
+    _watcher: Arc<Mutex<RecommendedWatcher>>,
+}
+
+impl V8Watcher {
+    /// Начинает отслеживание для общего [V8Finder]. Повторный поиск выполняется через 500 мс
+    /// после последнего изменения
+    pub fn new(finder: Arc<RwLock<V8Finder>>) -> Result<Self, V8FindError> {
+        V8Watcher::with_debounce(finder, DEFAULT_DEBOUNCE)
+    }
+
+    /// Аналог [V8Watcher::new] с заданной паузой перед повторным поиском
+    pub fn with_debounce(finder: Arc<RwLock<V8Finder>>, debounce: Duration) -> Result<Self, V8FindError> {
+        let (raw_sender, raw_events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(raw_sender).map_err(|err| watch_error(None, err))?;
+        let watcher = Arc::new(Mutex::new(watcher));
+        let (sender, events) = mpsc::channel();
+
+        let (snapshot, targets, config_files) = {
+            let finder = read(&finder);
+            (finder.snapshot(), watch_targets(&finder), finder.config_files())
+        };
+        let mut state = WatchState {
+            finder: Arc::clone(&finder),
+            watcher: Arc::downgrade(&watcher),
+            sender,
+            debounce,
+            snapshot,
+            targets: BTreeSet::new(),
+            config_files,
+        };
+        state.update_targets(&mut lock(&watcher), targets);
+
+        thread::Builder::new()
+            .name("v8find-watcher".to_owned())
+            .spawn(move || state.run(raw_events))
+            .map_err(|err| V8FindError::Watch { path: None, message: err.to_string() })?;
+
+        Ok(V8Watcher { finder, events, _watcher: watcher })
+    }
+
+    /// Общий [V8Finder], который обновляется при изменениях
+    pub fn finder(&self) -> &Arc<RwLock<V8Finder>> {
+        &self.finder
+    }
+
+    /// Канал обнаруженных изменений. Для каждого повторного поиска сначала передаются изменения
+    /// файлов настроек, затем удаленные, добавленные и измененные платформы
+    pub fn events(&self) -> &Receiver<V8WatchEvent> {
+        &self.events
+    }
+}
+
+impl V8Finder {
+    /// Начинает отслеживание изменений, см. [V8Watcher]. Найденные платформы доступны через
+    /// [V8Watcher::finder]
+    pub fn watch(self) -> Result<V8Watcher, V8FindError> {
+        V8Watcher::new(Arc::new(RwLock::new(self)))
+    }
+}
+
+struct WatchState {
+    finder: Arc<RwLock<V8Finder>>,
+    watcher: Weak<Mutex<RecommendedWatcher>>,
+    sender: Sender<V8WatchEvent>,
+    debounce: Duration,
+    snapshot: V8Snapshot,
+    targets: BTreeSet<PathBuf>,
+    config_files: Vec<PathBuf>,
+}
+
+impl WatchState {
+    fn run(mut self, raw_events: Receiver<notify::Result<Event>>) {
+        while let Ok(first_event) = raw_events.recv() {
+            let mut batch = vec![first_event];
+            loop {
+                match raw_events.recv_timeout(self.debounce) {
+                    Ok(event) => batch.push(event),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+
+            if let Some(paths) = changed_paths(batch) {
+                if !self.rescan(&paths) {
+                    return;
+                }
+            }
+        }
+    }
+
+    // Возвращает false, если отслеживание прекращено
+    fn rescan(&mut self, paths: &BTreeSet<PathBuf>) -> bool {
+        let mut events: Vec<_> = self.config_files.iter()
+            .filter(|config_file| paths.contains(*config_file))
+            .cloned()
+            .map(V8WatchEvent::StarterConfigChanged)
+            .collect();
+
+        // Поиск выполняется под блокировкой на чтение, чтобы не мешать пользователям общего V8Finder
+        let rescanned = read(&self.finder).rescan();
+        let snapshot = rescanned.snapshot();
+        let targets = watch_targets(&rescanned);
+        self.config_files = rescanned.config_files();
+        *self.finder.write().unwrap_or_else(PoisonError::into_inner) = rescanned;
+
+        let diff = self.snapshot.diff(&snapshot);
+        self.snapshot = snapshot;
+        events.extend(diff.removed.into_iter().map(V8WatchEvent::PlatformRemoved));
+        events.extend(diff.added.into_iter().map(V8WatchEvent::PlatformAdded));
+        events.extend(diff.changed.into_iter().map(V8WatchEvent::PlatformChanged));
+
+        match self.watcher.upgrade() {
+            Some(watcher) => self.update_targets(&mut lock(&watcher), targets),
+            None => return false,
+        }
+        events.into_iter().all(|event| self.sender.send(event).is_ok())
+    }
+
+    // Каталоги, которые не удалось начать отслеживать, не запоминаются, чтобы попробовать снова
+    // при следующем повторном поиске
+    fn update_targets(&mut self, watcher: &mut RecommendedWatcher, targets: BTreeSet<PathBuf>) {
+        for removed in self.targets.difference(&targets) {
+            // Удаленный каталог перестает отслеживаться сам, ошибка в этом случае ожидаема
+            let _ = watcher.unwatch(removed);
+        }
+
+        let mut watched = BTreeSet::new();
+        for target in targets {
+            if self.targets.contains(&target) {
+                watched.insert(target);
+                continue;
+            }
+            match watcher.watch(&target, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    watched.insert(target);
+                }
+                Err(err) => warn!("{}", watch_error(Some(&target), err)),
+            }
+        }
+        self.targets = watched;
+    }
+}
+
+// Отслеживаются корневые каталоги (появление и удаление каталогов версий), каталоги платформ и их bin
+// (копирование исполняемых файлов), а также каталоги файлов 1CEStart.cfg, так как файл может появиться
+// позже или быть заменен целиком. Вместо несуществующего каталога отслеживается ближайший существующий
+// родительский каталог
+fn watch_targets(finder: &V8Finder) -> BTreeSet<PathBuf> {
+    let env = finder.env.as_ref();
+    let existing = |path: &Path| path.ancestors()
+        .find(|ancestor| env.exists(ancestor))
+        .map(Path::to_path_buf);
+
+    let roots = finder.roots().iter().filter_map(|root| existing(root));
+    let platform_dirs = finder.platforms().iter()
+        .flat_map(|platform| vec![platform.path().to_path_buf(), platform.path().join("bin")])
+        .filter(|dir| env.exists(dir));
+    let config_dirs = finder.config_files().into_iter()
+        .filter_map(|config_file| config_file.parent().and_then(existing));
+
+    roots.chain(platform_dirs).chain(config_dirs).collect()
+}
+
+// Чтение файлов при повторном поиске само порождает события доступа, их нужно пропускать
+fn changed_paths(batch: Vec<notify::Result<Event>>) -> Option<BTreeSet<PathBuf>> {
+    let mut changed = false;
+    let mut paths = BTreeSet::new();
+    for event in batch {
+        match event {
+            Ok(event) => {
+                let is_access = matches!(event.kind,
+                    EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(MetadataKind::AccessTime)));
+                if !is_access || event.need_rescan() {
+                    changed = true;
+                    paths.extend(event.paths);
+                }
+            }
+            Err(err) => warn!("{}", watch_error(None, err)),
+        }
+    }
+
+    if changed {
+        Some(paths)
+    } else {
+        None
+    }
+}
+
+fn watch_error(path: Option<&Path>, mut err: notify::Error) -> V8FindError {
+    let path = path.map(Path::to_path_buf).or_else(|| err.paths.first().cloned());
+    // Путь хранится отдельно, в тексте ошибки он не нужен
+    err.paths.clear();
+    V8FindError::Watch { path, message: err.to_string() }
+}
+
+fn read(finder: &RwLock<V8Finder>) -> RwLockReadGuard<'_, V8Finder> {
+    finder.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn lock(watcher: &Mutex<RecommendedWatcher>) -> MutexGuard<'_, RecommendedWatcher> {
+    watcher.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use notify::event::{AccessKind, CreateKind, Flag, RemoveKind};
+
+    use super::*;
+    use crate::v8_env::MemoryEnvironment;
+
+    #[test]
+    fn targets_are_existing_directories() {
+        let env = MemoryEnvironment::new("linux")
+            .with_home("/home/user")
+            .with_dir("/home/user")
+            .with_file("/etc/1C/1cestart/1cestart.cfg", "InstalledLocation=/mnt/1c\n")
+            .with_file("/opt/1cv8/x86_64/8.3.22.1709/1cv8", "")
+            .with_dir("/mnt/1c/8.3.23.1865/bin")
+            .with_dir("/srv");
+        let finder = V8Finder::builder()
+            .default_paths(false)
+            .roots(["/opt/1cv8/x86_64", "/srv/1c/x86_64"])
+            .environment(env)
+            .build();
+
+        let targets: Vec<_> = watch_targets(&finder).into_iter().collect();
+        assert_eq!(targets, [
+            "/etc/1C/1cestart", "/home/user", "/mnt/1c", "/mnt/1c/8.3.23.1865", "/mnt/1c/8.3.23.1865/bin",
+            "/opt/1cv8/x86_64", "/opt/1cv8/x86_64/8.3.22.1709", "/srv",
+        ].map(PathBuf::from));
+    }
+
+    #[test]
+    fn access_events_are_skipped() {
+        let event = |kind: EventKind, path: &str| Ok(Event::new(kind).add_path(path.into()));
+        let access = || event(EventKind::Access(AccessKind::Any), "/opt/1cv8/x86_64/8.3.22.1709/1cv8");
+        let access_time = || event(EventKind::Modify(ModifyKind::Metadata(MetadataKind::AccessTime)), "/opt/1cv8");
+
+        assert_eq!(changed_paths(vec![access(), access_time()]), None);
+        assert_eq!(changed_paths(vec![Err(notify::Error::generic("queue overflow"))]), None);
+
+        let changed = changed_paths(vec![
+            access(),
+            event(EventKind::Create(CreateKind::Folder), "/opt/1cv8/x86_64/8.3.23.1865"),
+            event(EventKind::Remove(RemoveKind::Folder), "/opt/1cv8/x86_64/8.3.21.1895"),
+        ]);
+        assert_eq!(changed, Some(["/opt/1cv8/x86_64/8.3.21.1895", "/opt/1cv8/x86_64/8.3.23.1865"]
+            .iter().map(PathBuf::from).collect()));
+
+        // Пропущенные события требуют повторного поиска, даже если пришли вместе с событием доступа
+        let rescan = Event::new(EventKind::Access(AccessKind::Any)).set_flag(Flag::Rescan);
+        assert_eq!(changed_paths(vec![Ok(rescan)]), Some(BTreeSet::new()));
+    }
+
+    #[test]
+    fn reports_added_and_removed_platforms() {
+        let root = tempfile::tempdir().unwrap();
+        let finder = V8Finder::builder().no_defaults().root(root.path()).build();
+        let watcher = V8Watcher::with_debounce(Arc::new(RwLock::new(finder)), Duration::from_millis(50)).unwrap();
+        let next_event = || watcher.events().recv_timeout(Duration::from_secs(10)).unwrap();
+
+        let v8_path = root.path().join("8.3.22.1709");
+        fs::create_dir(&v8_path).unwrap();
+        match next_event() {
+            V8WatchEvent::PlatformAdded(platform) => assert_eq!(platform.path(), v8_path),
+            event => panic!("unexpected {:?}", event),
+        }
+        assert_eq!(read(watcher.finder()).platforms().len(), 1);
+
+        fs::remove_dir(&v8_path).unwrap();
+        match next_event() {
+            V8WatchEvent::PlatformRemoved(platform) => assert_eq!(platform.path(), v8_path),
+            event => panic!("unexpected {:?}", event),
+        }
+        assert!(read(watcher.finder()).platforms().is_empty());
+    }
+}
@@ -0,0 +1,498 @@
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use encoding_rs::{UTF_16LE, WINDOWS_1251};
+
+use crate::v8_error::V8FindError;
+
+/// Кодировка файла 1CEStart.cfg. Сохраняется при разборе, чтобы файл был записан обратно в том же виде
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StarterEncoding {
+    /// UTF-16LE с BOM - кодировка, в которой файл записывает сама платформа в Windows
+    Utf16LeBom,
+    /// UTF-8 с BOM
+    Utf8Bom,
+    /// UTF-8 без BOM
+    Utf8,
+    /// Windows-1251
+    Windows1251,
+}
+
+/// Строка файла 1CEStart.cfg
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StarterEntry {
+    /// Версия платформы, запускаемая по умолчанию
+    DefaultVersion(String),
+    /// Каталог шаблонов конфигураций
+    ConfigurationTemplatesLocation(PathBuf),
+    /// Каталог публикации дистрибутива
+    PublishDistributiveLocation(PathBuf),
+    /// Общий список информационных баз
+    CommonInfoBases(PathBuf),
+    /// Использовать аппаратную лицензию
+    UseHWLicenses(bool),
+    /// Автоматически устанавливать последнюю версию
+    AppAutoInstallLastVersion(bool),
+    /// Каталог установки платформы. Параметр может повторяться
+    InstalledLocation(PathBuf),
+    /// Параметр, неизвестный библиотеке, либо параметр с некорректным значением
+    Other {
+        /// Имя параметра
+        key: String,
+        /// Значение параметра
+        value: String,
+    },
+    /// Строка без знака `=`, например, пустая
+    Raw(String),
+}
+
+impl StarterEntry {
+    fn parse(line: &str) -> Self {
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value),
+            None => return StarterEntry::Raw(line.to_owned()),
+        };
+
+        let entry = match key {
+            "DefaultVersion" => Some(StarterEntry::DefaultVersion(value.to_owned())),
+            "ConfigurationTemplatesLocation" => Some(StarterEntry::ConfigurationTemplatesLocation(value.into())),
+            "PublishDistributiveLocation" => Some(StarterEntry::PublishDistributiveLocation(value.into())),
+            "CommonInfoBases" => Some(StarterEntry::CommonInfoBases(value.into())),
+            "UseHWLicenses" => parse_flag(value).map(StarterEntry::UseHWLicenses),
+            "AppAutoInstallLastVersion" => parse_flag(value).map(StarterEntry::AppAutoInstallLastVersion),
+            "InstalledLocation" => Some(StarterEntry::InstalledLocation(value.into())),
+            _ => None,
+        };
+
+        entry.unwrap_or_else(|| StarterEntry::Other {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+
+    fn to_line(&self) -> String {
+        match self {
+            StarterEntry::DefaultVersion(version) => format!("DefaultVersion={}", version),
+            StarterEntry::ConfigurationTemplatesLocation(path) => {
+                format!("ConfigurationTemplatesLocation={}", path.display())
+            }
+            StarterEntry::PublishDistributiveLocation(path) => {
+                format!("PublishDistributiveLocation={}", path.display())
+            }
+            StarterEntry::CommonInfoBases(path) => format!("CommonInfoBases={}", path.display()),
+            StarterEntry::UseHWLicenses(flag) => format!("UseHWLicenses={}", format_flag(*flag)),
+            StarterEntry::AppAutoInstallLastVersion(flag) => {
+                format!("AppAutoInstallLastVersion={}", format_flag(*flag))
+            }
+            StarterEntry::InstalledLocation(path) => format!("InstalledLocation={}", path.display()),
+            StarterEntry::Other { key, value } => format!("{}={}", key, value),
+            StarterEntry::Raw(line) => line.clone(),
+        }
+    }
+}
+
+/// Содержимое файла настроек стартера 1CEStart.cfg. Порядок строк, неизвестные параметры, кодировка
+/// и вид перевода строки сохраняются, так что файл можно изменить и записать обратно.
+///```
+/// use v8find4rs::v8_starter::StarterConfig;
+///
+/// let mut config = StarterConfig::parse(b"DefaultVersion=8.3\r\nInstalledLocation=C:\\1cv8\r\n");
+/// config.add_installed_location(r"D:\1cv8");
+/// config.set_default_version(Some("8.3.22.1709"));
+///
+/// assert_eq!(config.installed_locations().len(), 2);
+/// assert_eq!(config.default_version(), Some("8.3.22.1709"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StarterConfig {
+    entries: Vec<StarterEntry>,
+    // Строки в том виде, в каком они были прочитаны, вместе с результатом разбора. Неизмененные
+    // строки записываются обратно без изменений, включая пробелы вокруг `=` и запись флагов
+    source_lines: Vec<(StarterEntry, String)>,
+    encoding: StarterEncoding,
+    line_ending: &'static str,
+    trailing_line_ending: bool,
+}
+
+impl StarterConfig {
+    /// Создание пустого файла настроек в указанной кодировке
+    pub fn new(encoding: StarterEncoding) -> Self {
+        StarterConfig {
+            entries: Vec::new(),
+            source_lines: Vec::new(),
+            encoding,
+            line_ending: "\r\n",
+            trailing_line_ending: true,
+        }
+    }
+
+    /// Разбирает содержимое файла. Кодировка определяется по BOM, при его отсутствии - проверкой
+    /// на корректность UTF-8, иначе файл считается записанным в Windows-1251
+    pub fn parse(bytes: &[u8]) -> Self {
+        let (text, encoding) = decode(bytes);
+        let line_ending = if text.contains("\r\n") { "\r\n" } else { "\n" };
+        let trailing_line_ending = text.ends_with('\n');
+
+        let source_lines: Vec<_> = text.lines()
+            .map(|line| (StarterEntry::parse(line), line.to_owned()))
+            .collect();
+
+        StarterConfig {
+            entries: source_lines.iter().map(|(entry, _)| entry.clone()).collect(),
+            source_lines,
+            encoding,
+            line_ending,
+            trailing_line_ending,
+        }
+    }
+
+    /// Читает и разбирает файл
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, V8FindError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)
+            .map_err(|source| V8FindError::StarterConfig { path: path.to_owned(), source })?;
+        Ok(StarterConfig::parse(&bytes))
+    }
+
+    /// Записывает файл в исходной кодировке
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), V8FindError> {
+        let path = path.as_ref();
+        fs::write(path, self.to_bytes())
+            .map_err(|source| V8FindError::StarterConfig { path: path.to_owned(), source })
+    }
+
+    /// Возвращает содержимое файла в исходной кодировке. Неизмененные строки записываются в исходном
+    /// виде, так что файл без изменений записывается обратно байт в байт. Символы, не представимые
+    /// в Windows-1251, заменяются числовыми ссылками вида `&#1234;`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut text = self.lines().join(self.line_ending);
+        if self.trailing_line_ending && !self.entries.is_empty() {
+            text.push_str(self.line_ending);
+        }
+
+        match self.encoding {
+            StarterEncoding::Utf16LeBom => {
+                let mut bytes = vec![0xFF, 0xFE];
+                bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+                bytes
+            }
+            StarterEncoding::Utf8Bom => {
+                let mut bytes = vec![0xEF, 0xBB, 0xBF];
+                bytes.extend(text.into_bytes());
+                bytes
+            }
+            StarterEncoding::Utf8 => text.into_bytes(),
+            StarterEncoding::Windows1251 => WINDOWS_1251.encode(&text).0.into_owned(),
+        }
+    }
+
+    /// Кодировка файла
+    pub fn encoding(&self) -> StarterEncoding {
+        self.encoding
+    }
+
+    /// Изменяет кодировку, в которой файл будет записан
+    pub fn set_encoding(&mut self, encoding: StarterEncoding) {
+        self.encoding = encoding;
+    }
+
+    /// Все строки файла в исходном порядке
+    pub fn entries(&self) -> &[StarterEntry] {
+        &self.entries
+    }
+
+    /// Все строки файла с возможностью изменения
+    pub fn entries_mut(&mut self) -> &mut Vec<StarterEntry> {
+        &mut self.entries
+    }
+
+    /// Значение DefaultVersion
+    pub fn default_version(&self) -> Option<&str> {
+        self.entries.iter().find_map(|entry| match entry {
+            StarterEntry::DefaultVersion(version) => Some(version.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Устанавливает или, если передан `None`, удаляет DefaultVersion
+    pub fn set_default_version(&mut self, version: Option<&str>) {
+        let entry = version.map(|version| StarterEntry::DefaultVersion(version.to_owned()));
+        self.replace(|entry| matches!(entry, StarterEntry::DefaultVersion(_)), entry);
+    }
+
+    /// Значение ConfigurationTemplatesLocation
+    pub fn configuration_templates_location(&self) -> Option<&Path> {
+        self.entries.iter().find_map(|entry| match entry {
+            StarterEntry::ConfigurationTemplatesLocation(path) => Some(path.as_path()),
+            _ => None,
+        })
+    }
+
+    /// Устанавливает или, если передан `None`, удаляет ConfigurationTemplatesLocation
+    pub fn set_configuration_templates_location<P: Into<PathBuf>>(&mut self, path: Option<P>) {
+        let entry = path.map(|path| StarterEntry::ConfigurationTemplatesLocation(path.into()));
+        self.replace(|entry| matches!(entry, StarterEntry::ConfigurationTemplatesLocation(_)), entry);
+    }
+
+    /// Значение PublishDistributiveLocation
+    pub fn publish_distributive_location(&self) -> Option<&Path> {
+        self.entries.iter().find_map(|entry| match entry {
+            StarterEntry::PublishDistributiveLocation(path) => Some(path.as_path()),
+            _ => None,
+        })
+    }
+
+    /// Устанавливает или, если передан `None`, удаляет PublishDistributiveLocation
+    pub fn set_publish_distributive_location<P: Into<PathBuf>>(&mut self, path: Option<P>) {
+        let entry = path.map(|path| StarterEntry::PublishDistributiveLocation(path.into()));
+        self.replace(|entry| matches!(entry, StarterEntry::PublishDistributiveLocation(_)), entry);
+    }
+
+    /// Все значения CommonInfoBases
+    pub fn common_info_bases(&self) -> Vec<&Path> {
+        self.entries.iter().filter_map(|entry| match entry {
+            StarterEntry::CommonInfoBases(path) => Some(path.as_path()),
+            _ => None,
+        }).collect()
+    }
+
+    /// Значение UseHWLicenses
+    pub fn use_hw_licenses(&self) -> Option<bool> {
+        self.entries.iter().find_map(|entry| match entry {
+            StarterEntry::UseHWLicenses(flag) => Some(*flag),
+            _ => None,
+        })
+    }
+
+    /// Устанавливает или, если передан `None`, удаляет UseHWLicenses
+    pub fn set_use_hw_licenses(&mut self, flag: Option<bool>) {
+        let entry = flag.map(StarterEntry::UseHWLicenses);
+        self.replace(|entry| matches!(entry, StarterEntry::UseHWLicenses(_)), entry);
+    }
+
+    /// Значение AppAutoInstallLastVersion
+    pub fn app_auto_install_last_version(&self) -> Option<bool> {
+        self.entries.iter().find_map(|entry| match entry {
+            StarterEntry::AppAutoInstallLastVersion(flag) => Some(*flag),
+            _ => None,
+        })
+    }
+
+    /// Устанавливает или, если передан `None`, удаляет AppAutoInstallLastVersion
+    pub fn set_app_auto_install_last_version(&mut self, flag: Option<bool>) {
+        let entry = flag.map(StarterEntry::AppAutoInstallLastVersion);
+        self.replace(|entry| matches!(entry, StarterEntry::AppAutoInstallLastVersion(_)), entry);
+    }
+
+    /// Все значения InstalledLocation
+    pub fn installed_locations(&self) -> Vec<&Path> {
+        self.entries.iter().filter_map(|entry| match entry {
+            StarterEntry::InstalledLocation(path) => Some(path.as_path()),
+            _ => None,
+        }).collect()
+    }
+
+    /// Добавляет InstalledLocation после уже имеющихся. Возвращает `false`, если такой каталог
+    /// уже указан в файле
+    pub fn add_installed_location<P: Into<PathBuf>>(&mut self, path: P) -> bool {
+        let path = path.into();
+        if self.installed_locations().contains(&path.as_path()) {
+            return false;
+        }
+
+        let position = self.entries.iter()
+            .rposition(|entry| matches!(entry, StarterEntry::InstalledLocation(_)))
+            .map_or(self.entries.len(), |position| position + 1);
+        self.entries.insert(position, StarterEntry::InstalledLocation(path));
+        true
+    }
+
+    /// Удаляет InstalledLocation. Возвращает `false`, если такого каталога в файле не было
+    pub fn remove_installed_location<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        let entries_count = self.entries.len();
+        self.entries.retain(|entry| !matches!(entry, StarterEntry::InstalledLocation(location)
+            if location.as_path() == path.as_ref()));
+        entries_count != self.entries.len()
+    }
+
+    /// Значение произвольного параметра в том виде, в каком оно записано в файле
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.lines().into_iter()
+            .find_map(|line| line.split_once('=')
+                .filter(|(line_key, _)| line_key.trim() == key)
+                .map(|(_, value)| value.to_owned()))
+    }
+
+    // Строка из исходного файла используется для первой равной ей записи, остальные записи
+    // (добавленные или измененные) форматируются заново
+    fn lines(&self) -> Vec<Cow<'_, str>> {
+        let mut unused: Vec<_> = self.source_lines.iter().collect();
+        self.entries.iter()
+            .map(|entry| match unused.iter().position(|(source_entry, _)| source_entry == entry) {
+                Some(position) => Cow::Borrowed(unused.remove(position).1.as_str()),
+                None => Cow::Owned(entry.to_line()),
+            })
+            .collect()
+    }
+
+    // Заменяет первую подходящую строку и удаляет остальные; если строк не было - добавляет в конец
+    fn replace<F: Fn(&StarterEntry) -> bool>(&mut self, is_target: F, entry: Option<StarterEntry>) {
+        let position = self.entries.iter().position(&is_target);
+        self.entries.retain(|existing| !is_target(existing));
+        if let Some(entry) = entry {
+            let position = position.unwrap_or(self.entries.len()).min(self.entries.len());
+            self.entries.insert(position, entry);
+        }
+    }
+}
+
+impl Default for StarterConfig {
+    fn default() -> Self {
+        StarterConfig::new(StarterEncoding::Utf16LeBom)
+    }
+}
+
+fn decode(bytes: &[u8]) -> (String, StarterEncoding) {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        let (text, _) = UTF_16LE.decode_without_bom_handling(utf16);
+        return (text.into_owned(), StarterEncoding::Utf16LeBom);
+    }
+    if let Some(utf8) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return (String::from_utf8_lossy(utf8).into_owned(), StarterEncoding::Utf8Bom);
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_owned(), StarterEncoding::Utf8),
+        Err(_) => {
+            let (text, _) = WINDOWS_1251.decode_without_bom_handling(bytes);
+            (text.into_owned(), StarterEncoding::Windows1251)
+        }
+    }
+}
+
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim() {
+        "1" => Some(true),
+        "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn format_flag(flag: bool) -> &'static str {
+    if flag { "1" } else { "0" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Пробелы вокруг `=`, флаг с пробелом, неизвестный параметр, пустая строка и строка без `=`
+    const CFG: &str = "DefaultVersion =8.3\r\n\
+        InstalledLocation=C:\\Program Files\\1cv8\r\n\
+        UseHWLicenses= 1\r\n\
+        \r\n\
+        ; комментарий\r\n\
+        InstalledLocation=D:\\Платформы\\1cv8\r\n\
+        AppAutoInstallLastVersion=yes\r\n\
+        CommonCfgLocation=C:\\Users\\Общие\\ibases.v8i\r\n";
+
+    fn encode(text: &str, encoding: StarterEncoding) -> Vec<u8> {
+        match encoding {
+            StarterEncoding::Utf16LeBom => {
+                let mut bytes = vec![0xFF, 0xFE];
+                bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+                bytes
+            }
+            StarterEncoding::Utf8Bom => [&[0xEF, 0xBB, 0xBF][..], text.as_bytes()].concat(),
+            StarterEncoding::Utf8 => text.as_bytes().to_vec(),
+            StarterEncoding::Windows1251 => WINDOWS_1251.encode(text).0.into_owned(),
+        }
+    }
+
+    const ENCODINGS: [StarterEncoding; 4] = [
+        StarterEncoding::Utf16LeBom, StarterEncoding::Utf8Bom, StarterEncoding::Utf8, StarterEncoding::Windows1251,
+    ];
+
+    #[test]
+    fn round_trips_byte_for_byte() {
+        for encoding in ENCODINGS {
+            for text in [CFG, &CFG.replace("\r\n", "\n"), CFG.trim_end(), ""] {
+                let bytes = encode(text, encoding);
+                let config = StarterConfig::parse(&bytes);
+                if !text.is_empty() {
+                    assert_eq!(config.encoding(), encoding);
+                }
+                assert_eq!(config.to_bytes(), bytes, "{:?}: {:?}", encoding, text);
+            }
+        }
+    }
+
+    #[test]
+    fn parses_entries() {
+        let config = StarterConfig::parse(&encode(CFG, StarterEncoding::Windows1251));
+        assert_eq!(config.default_version(), Some("8.3"));
+        assert_eq!(config.use_hw_licenses(), Some(true));
+        assert_eq!(config.app_auto_install_last_version(), None);
+        assert_eq!(config.installed_locations(),
+                   [Path::new(r"C:\Program Files\1cv8"), Path::new(r"D:\Платформы\1cv8")]);
+        assert_eq!(config.get("AppAutoInstallLastVersion").as_deref(), Some("yes"));
+        assert_eq!(config.get("UseHWLicenses").as_deref(), Some(" 1"));
+        assert_eq!(config.entries()[3], StarterEntry::Raw(String::new()));
+    }
+
+    #[test]
+    fn add_installed_location_keeps_other_lines() {
+        for encoding in ENCODINGS {
+            let mut config = StarterConfig::parse(&encode(CFG, encoding));
+            assert!(config.add_installed_location(r"E:\1cv8"));
+            assert!(!config.add_installed_location(r"C:\Program Files\1cv8"));
+
+            let expected = CFG.replace("D:\\Платформы\\1cv8\r\n",
+                                       "D:\\Платформы\\1cv8\r\nInstalledLocation=E:\\1cv8\r\n");
+            assert_eq!(config.to_bytes(), encode(&expected, encoding), "{:?}", encoding);
+
+            assert!(config.remove_installed_location(r"C:\Program Files\1cv8"));
+            assert!(!config.remove_installed_location(r"C:\Program Files\1cv8"));
+            let expected = expected.replace("InstalledLocation=C:\\Program Files\\1cv8\r\n", "");
+            assert_eq!(config.to_bytes(), encode(&expected, encoding), "{:?}", encoding);
+        }
+    }
+
+    #[test]
+    fn add_installed_location_to_file_without_locations() {
+        let mut config = StarterConfig::parse(b"DefaultVersion=8.3\n");
+        assert!(config.add_installed_location("/opt/1cv8"));
+        assert_eq!(config.to_bytes(), b"DefaultVersion=8.3\nInstalledLocation=/opt/1cv8\n");
+
+        let mut config = StarterConfig::new(StarterEncoding::Utf8);
+        assert!(config.add_installed_location("/opt/1cv8"));
+        assert_eq!(config.to_bytes(), b"InstalledLocation=/opt/1cv8\r\n");
+    }
+
+    #[test]
+    fn set_default_version_in_place() {
+        for encoding in ENCODINGS {
+            let mut config = StarterConfig::parse(&encode(CFG, encoding));
+            config.set_default_version(Some("8.3.22.1709"));
+            let expected = CFG.replace("DefaultVersion =8.3", "DefaultVersion=8.3.22.1709");
+            assert_eq!(config.to_bytes(), encode(&expected, encoding), "{:?}", encoding);
+
+            config.set_default_version(None);
+            assert_eq!(config.default_version(), None);
+            let expected = CFG.replace("DefaultVersion =8.3\r\n", "");
+            assert_eq!(config.to_bytes(), encode(&expected, encoding), "{:?}", encoding);
+        }
+
+        let mut config = StarterConfig::parse(b"InstalledLocation=/opt/1cv8\n");
+        config.set_default_version(Some("8.3"));
+        assert_eq!(config.to_bytes(), b"InstalledLocation=/opt/1cv8\nDefaultVersion=8.3\n");
+    }
+
+    #[test]
+    fn changing_entry_in_place_rewrites_only_that_line() {
+        let mut config = StarterConfig::parse(CFG.as_bytes());
+        config.entries_mut()[2] = StarterEntry::UseHWLicenses(false);
+        let expected = CFG.replace("UseHWLicenses= 1", "UseHWLicenses=0");
+        assert_eq!(config.to_bytes(), expected.as_bytes());
+    }
+}
@@ -0,0 +1,521 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Полная версия платформы 1С, например `8.3.22.1709`.
+///
+/// Порядок сравнения совпадает с порядком компонент: `major`, `minor`, `release`, `build`.
+///```
+/// use v8find4rs::v8_version::V8Version;
+///
+/// let version: V8Version = "8.3.22-1709".parse().unwrap();
+/// assert_eq!(version, V8Version::new(8, 3, 22, 1709));
+/// assert_eq!(version.to_string(), "8.3.22.1709");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct V8Version {
+    /// 8.3.22.1709 <- 8
+    pub major: u32,
+    /// 8.3.22.1709 <- 3
+    pub minor: u32,
+    /// 8.3.22.1709 <- 22
+    pub release: u32,
+    /// 8.3.22.1709 <- 1709
+    pub build: u32,
+}
+
+impl V8Version {
+    /// Создает версию из отдельных компонент
+    pub fn new(major: u32, minor: u32, release: u32, build: u32) -> Self {
+        V8Version {
+            major,
+            minor,
+            release,
+            build,
+        }
+    }
+
+    /// Возвращает компоненты версии в порядке `major`, `minor`, `release`, `build`
+    pub fn parts(&self) -> [u32; 4] {
+        [self.major, self.minor, self.release, self.build]
+    }
+
+    /// Проверяет, что версия начинается с переданных компонент. Пустой префикс подходит любой версии,
+    /// префикс длиннее четырех компонент - никакой.
+    pub fn starts_with(&self, prefix: &[u32]) -> bool {
+        prefix.len() <= 4 && self.parts()[..prefix.len()] == *prefix
+    }
+}
+
+impl fmt::Display for V8Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}.{}", self.major, self.minor, self.release, self.build)
+    }
+}
+
+impl FromStr for V8Version {
+    type Err = ParseVersionError;
+
+    /// Разбирает версию в одном из встречающихся на практике форматов:
+    /// * `8.3.22.1709` - имя каталога платформы;
+    /// * `8_3_22_1709` - имя дистрибутива;
+    /// * `8.3.22-1709` - версия deb-пакета.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = parse_parts(s)?;
+        match parts.as_slice() {
+            [major, minor, release, build] => Ok(V8Version::new(*major, *minor, *release, *build)),
+            _ => Err(ParseVersionError::new(s)),
+        }
+    }
+}
+
+/// Разбирает от одной до четырех числовых компонент версии, разделенных `.`, `_` или `-`. Компонента
+/// должна состоять только из цифр: `u32::from_str` допускает знак `+`, а `+8.3.1.1` версией не является
+pub(crate) fn parse_parts(s: &str) -> Result<Vec<u32>, ParseVersionError> {
+    let trimmed = s.trim();
+    let parts: Vec<u32> = trimmed
+        .split(['.', '_', '-'])
+        .map(|part| {
+            if !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()) {
+                part.parse().ok()
+            } else {
+                None
+            }
+        })
+        .collect::<Option<_>>()
+        .ok_or_else(|| ParseVersionError::new(s))?;
+
+    if parts.len() > 4 {
+        return Err(ParseVersionError::new(s));
+    }
+    Ok(parts)
+}
+
+/// Ошибка разбора версии платформы из строки
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseVersionError {
+    input: String,
+}
+
+impl ParseVersionError {
+    pub(crate) fn new(input: &str) -> Self {
+        ParseVersionError {
+            input: input.to_owned(),
+        }
+    }
+
+    /// Строка, которую не удалось разобрать
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+}
+
+impl fmt::Display for ParseVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid 1C platform version: \"{}\"", self.input)
+    }
+}
+
+impl Error for ParseVersionError {}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for V8Version {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for V8Version {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let version = String::deserialize(deserializer)?;
+        version.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Требование к версии платформы. Состоит из одного или нескольких условий, перечисленных через запятую;
+/// версия удовлетворяет требованию, если удовлетворяет всем условиям сразу.
+///
+/// Поддерживаемые условия:
+/// * `8.3`, `8.3.22`, `=8.3.22.1709` - версия начинается с указанных компонент;
+/// * `8.3.2*`, `8.3.*` - последняя компонента задана шаблоном: `2*` соответствует релизам 2, 20-29, 200-299 и т.д.;
+/// * `>=8.3.18`, `>8.3.18`, `<8.3.23`, `<=8.3.22` - сравнение с версией, отсутствующие компоненты
+///   не учитываются: `<=8.3.22` включает любую сборку 8.3.22;
+/// * `~8.3.20` - любая сборка 8.3.20, `~8.3.20.1500` - сборка 8.3.20 не ниже 1500;
+/// * `^8.3.18` - версия не ниже 8.3.18 в пределах 8.3;
+/// * `latest` или `*` - любая версия.
+///```
+/// use v8find4rs::v8_version::{V8Version, V8VersionReq};
+///
+/// let req: V8VersionReq = ">=8.3.18, <8.3.23".parse().unwrap();
+/// assert!(req.matches(&V8Version::new(8, 3, 22, 1709)));
+/// assert!(!req.matches(&V8Version::new(8, 3, 23, 1)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct V8VersionReq {
+    comparators: Vec<Comparator>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Op {
+    Prefix,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Tilde,
+    Caret,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Comparator {
+    op: Op,
+    parts: Vec<u32>,
+    // Текстовый префикс компоненты, следующей за `parts`: "2" для `8.3.2*`, "" для `8.3.*`
+    wildcard: Option<String>,
+}
+
+impl V8VersionReq {
+    /// Требование, которому удовлетворяет любая версия
+    pub fn any() -> Self {
+        V8VersionReq {
+            comparators: Vec::new(),
+        }
+    }
+
+    /// Требование, которому удовлетворяет только переданная версия
+    pub fn exact(version: &V8Version) -> Self {
+        V8VersionReq {
+            comparators: vec![Comparator {
+                op: Op::Prefix,
+                parts: version.parts().to_vec(),
+                wildcard: None,
+            }],
+        }
+    }
+
+    /// Проверяет, удовлетворяет ли версия требованию
+    pub fn matches(&self, version: &V8Version) -> bool {
+        self.comparators
+            .iter()
+            .all(|comparator| comparator.matches(version))
+    }
+}
+
+impl Comparator {
+    fn parse(input: &str, comparator: &str) -> Result<Self, ParseVersionReqError> {
+        let (op, pattern) = [
+            (">=", Op::GreaterEq),
+            ("<=", Op::LessEq),
+            (">", Op::Greater),
+            ("<", Op::Less),
+            ("=", Op::Prefix),
+            ("~", Op::Tilde),
+            ("^", Op::Caret),
+        ]
+        .iter()
+        .find(|(prefix, _)| comparator.starts_with(prefix))
+        .map(|(prefix, op)| (*op, comparator[prefix.len()..].trim()))
+        .unwrap_or((Op::Prefix, comparator));
+
+        let error = || ParseVersionReqError::new(input);
+        let mut parts = Vec::new();
+        let mut wildcard = None;
+
+        let components: Vec<&str> = pattern.split(['.', '_', '-']).collect();
+        for (index, component) in components.iter().enumerate() {
+            let is_last = index + 1 == components.len();
+            if let Some(prefix) = component.strip_suffix('*') {
+                if !is_last || !prefix.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(error());
+                }
+                wildcard = Some(prefix.to_owned());
+            } else if *component == "x" || *component == "X" {
+                if !is_last {
+                    return Err(error());
+                }
+                wildcard = Some(String::new());
+            } else if !component.is_empty() && component.chars().all(|c| c.is_ascii_digit()) {
+                parts.push(component.parse().map_err(|_| error())?);
+            } else {
+                return Err(error());
+            }
+        }
+
+        let component_count = parts.len() + wildcard.iter().count();
+        if component_count == 0 || component_count > 4 {
+            return Err(error());
+        }
+        if wildcard.is_some() && op != Op::Prefix {
+            return Err(error());
+        }
+
+        Ok(Comparator { op, parts, wildcard })
+    }
+
+    fn matches(&self, version: &V8Version) -> bool {
+        let version_parts = version.parts();
+        let count = self.parts.len();
+        let truncated = &version_parts[..count];
+
+        match self.op {
+            Op::Prefix => {
+                truncated == self.parts.as_slice()
+                    && self.wildcard.as_ref().is_none_or(|prefix| {
+                        version_parts[count].to_string().starts_with(prefix.as_str())
+                    })
+            }
+            Op::Greater => truncated > self.parts.as_slice(),
+            Op::GreaterEq => truncated >= self.parts.as_slice(),
+            Op::Less => truncated < self.parts.as_slice(),
+            Op::LessEq => truncated <= self.parts.as_slice(),
+            Op::Tilde => {
+                truncated >= self.parts.as_slice() && version.starts_with(&self.parts[..count.min(3)])
+            }
+            Op::Caret => {
+                truncated >= self.parts.as_slice() && version.starts_with(&self.parts[..count.min(2)])
+            }
+        }
+    }
+}
+
+impl fmt::Display for Comparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.op {
+            Op::Prefix => "",
+            Op::Greater => ">",
+            Op::GreaterEq => ">=",
+            Op::Less => "<",
+            Op::LessEq => "<=",
+            Op::Tilde => "~",
+            Op::Caret => "^",
+        };
+        let mut components: Vec<String> = self.parts.iter().map(u32::to_string).collect();
+        if let Some(prefix) = &self.wildcard {
+            components.push(format!("{}*", prefix));
+        }
+        write!(f, "{}{}", op, components.join("."))
+    }
+}
+
+impl fmt::Display for V8VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.comparators.is_empty() {
+            return write!(f, "*");
+        }
+        let comparators: Vec<String> = self.comparators.iter().map(Comparator::to_string).collect();
+        write!(f, "{}", comparators.join(", "))
+    }
+}
+
+impl FromStr for V8VersionReq {
+    type Err = ParseVersionReqError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        if trimmed == "*" || trimmed.eq_ignore_ascii_case("latest") {
+            return Ok(V8VersionReq::any());
+        }
+
+        let comparators = trimmed
+            .split(',')
+            .map(|comparator| Comparator::parse(s, comparator.trim()))
+            .collect::<Result<_, _>>()?;
+
+        Ok(V8VersionReq { comparators })
+    }
+}
+
+impl From<V8Version> for V8VersionReq {
+    fn from(version: V8Version) -> Self {
+        V8VersionReq::exact(&version)
+    }
+}
+
+/// Значение, которое можно использовать как требование к версии в
+/// [V8Finder::get_platform](crate::v8_finder::V8Finder::get_platform): строка в формате [V8VersionReq],
+/// [V8Version] или сам [V8VersionReq]. Разбирается только строка, версия и требование используются как есть
+pub trait IntoV8VersionReq {
+    /// Преобразует значение в требование к версии
+    fn into_version_req(self) -> Result<V8VersionReq, ParseVersionReqError>;
+}
+
+impl IntoV8VersionReq for &str {
+    fn into_version_req(self) -> Result<V8VersionReq, ParseVersionReqError> {
+        self.parse()
+    }
+}
+
+impl IntoV8VersionReq for String {
+    fn into_version_req(self) -> Result<V8VersionReq, ParseVersionReqError> {
+        self.parse()
+    }
+}
+
+impl IntoV8VersionReq for &String {
+    fn into_version_req(self) -> Result<V8VersionReq, ParseVersionReqError> {
+        self.parse()
+    }
+}
+
+impl IntoV8VersionReq for V8Version {
+    fn into_version_req(self) -> Result<V8VersionReq, ParseVersionReqError> {
+        Ok(V8VersionReq::exact(&self))
+    }
+}
+
+impl IntoV8VersionReq for &V8Version {
+    fn into_version_req(self) -> Result<V8VersionReq, ParseVersionReqError> {
+        Ok(V8VersionReq::exact(self))
+    }
+}
+
+impl IntoV8VersionReq for V8VersionReq {
+    fn into_version_req(self) -> Result<V8VersionReq, ParseVersionReqError> {
+        Ok(self)
+    }
+}
+
+impl IntoV8VersionReq for &V8VersionReq {
+    fn into_version_req(self) -> Result<V8VersionReq, ParseVersionReqError> {
+        Ok(self.clone())
+    }
+}
+
+/// Ошибка разбора требования к версии платформы
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseVersionReqError {
+    input: String,
+}
+
+impl ParseVersionReqError {
+    fn new(input: &str) -> Self {
+        ParseVersionReqError {
+            input: input.to_owned(),
+        }
+    }
+
+    /// Строка, которую не удалось разобрать
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+}
+
+impl fmt::Display for ParseVersionReqError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid 1C platform version requirement: \"{}\"", self.input)
+    }
+}
+
+impl Error for ParseVersionReqError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_supported_version_formats() {
+        let expected = V8Version::new(8, 3, 22, 1709);
+        assert_eq!("8.3.22.1709".parse::<V8Version>(), Ok(expected));
+        assert_eq!("8_3_22_1709".parse::<V8Version>(), Ok(expected));
+        assert_eq!("8.3.22-1709".parse::<V8Version>(), Ok(expected));
+        assert_eq!(" 8.3.22.1709 ".parse::<V8Version>(), Ok(expected));
+    }
+
+    #[test]
+    fn rejects_invalid_versions() {
+        for input in ["", "8.3.22", "8.3.22.1709.1", "8.3.x.1709", "8..22.1709", "+8.3.1.1", "8.3.+1.1", "8.3.-1.1"] {
+            assert_eq!(input.parse::<V8Version>(), Err(ParseVersionError::new(input)), "{}", input);
+        }
+    }
+
+    fn req(input: &str) -> V8VersionReq {
+        input.parse().unwrap_or_else(|err| panic!("{}: {}", input, err))
+    }
+
+    fn matches(input: &str, version: &str) -> bool {
+        req(input).matches(&version.parse().unwrap())
+    }
+
+    #[test]
+    fn prefix_and_exact_requirements() {
+        assert!(matches("8.3", "8.3.22.1709"));
+        assert!(matches("=8.3.22", "8.3.22.1709"));
+        assert!(!matches("8.3.2", "8.3.22.1709"));
+        assert!(matches("8.3.22.1709", "8.3.22.1709"));
+        assert!(!matches("8.3.22.1709", "8.3.22.1710"));
+        assert!(V8VersionReq::exact(&V8Version::new(8, 3, 22, 1709)).matches(&V8Version::new(8, 3, 22, 1709)));
+    }
+
+    #[test]
+    fn wildcard_requirements() {
+        assert!(matches("8.3.2*", "8.3.2.1"));
+        assert!(matches("8.3.2*", "8.3.24.1"));
+        assert!(matches("8.3.2*", "8.3.210.1"));
+        assert!(!matches("8.3.2*", "8.3.12.1"));
+        assert!(matches("8.3.*", "8.3.12.1"));
+        assert!(matches("8.3.x", "8.3.12.1"));
+        assert!(!matches("8.3.*", "8.2.19.1"));
+    }
+
+    #[test]
+    fn comparison_requirements() {
+        assert!(matches(">=8.3.18", "8.3.18.1"));
+        assert!(!matches(">8.3.18", "8.3.18.1"));
+        assert!(matches(">8.3.18", "8.3.19.1"));
+        assert!(matches("<=8.3.22", "8.3.22.9999"));
+        assert!(!matches("<8.3.23", "8.3.23.1"));
+        assert!(matches(">=8.3.18, <8.3.23", "8.3.22.1709"));
+        assert!(!matches(">=8.3.18, <8.3.23", "8.3.17.1"));
+    }
+
+    #[test]
+    fn tilde_and_caret_requirements() {
+        assert!(matches("~8.3.20", "8.3.20.2000"));
+        assert!(!matches("~8.3.20", "8.3.21.1"));
+        assert!(matches("~8.3.20.1500", "8.3.20.1500"));
+        assert!(!matches("~8.3.20.1500", "8.3.20.1499"));
+        assert!(matches("^8.3.18", "8.3.24.1"));
+        assert!(!matches("^8.3.18", "8.3.17.1"));
+        assert!(!matches("^8.3.18", "8.4.1.1"));
+    }
+
+    #[test]
+    fn any_requirements() {
+        for input in ["*", "latest", "LATEST", " * "] {
+            assert_eq!(req(input), V8VersionReq::any());
+        }
+        assert!(V8VersionReq::any().matches(&V8Version::new(8, 2, 19, 130)));
+    }
+
+    #[test]
+    fn rejects_invalid_requirements() {
+        for input in ["", "8.3.1.1.1", "8.*.3", ">=8.3.*", "8.3.a", "+8.3", "8.3.+1", "8..3", ">=8.3,"] {
+            assert!(input.parse::<V8VersionReq>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn requirement_display_round_trips() {
+        for input in [">=8.3.18, <8.3.23", "8.3.2*", "~8.3.20.1500", "^8.3", "*"] {
+            assert_eq!(req(&req(input).to_string()), req(input));
+        }
+    }
+
+    #[test]
+    fn typed_values_are_not_reparsed() {
+        let version = V8Version::new(8, 3, 22, 1709);
+        assert_eq!(version.into_version_req(), Ok(V8VersionReq::exact(&version)));
+        assert_eq!((&version).into_version_req(), Ok(V8VersionReq::exact(&version)));
+        assert_eq!(req("~8.3.20").into_version_req(), Ok(req("~8.3.20")));
+        assert_eq!((&req("^8.3")).into_version_req(), Ok(req("^8.3")));
+        assert_eq!("8.3".to_owned().into_version_req(), Ok(req("8.3")));
+        assert_eq!("8.3.a".into_version_req(), Err(ParseVersionReqError::new("8.3.a")));
+    }
+
+    #[test]
+    fn orders_by_components() {
+        assert!(V8Version::new(8, 3, 22, 1709) > V8Version::new(8, 3, 21, 2000));
+        assert!(V8Version::new(8, 3, 9, 1) < V8Version::new(8, 3, 10, 1));
+    }
+}